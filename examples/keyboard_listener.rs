@@ -1,4 +1,4 @@
-use crosskey::KeyboardListener;
+use crosskey::KeyboardListenerBuilder;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
@@ -7,8 +7,10 @@ fn main() {
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
 
-    let keyboard_listener =
-        KeyboardListener::attatch(&window).expect("failed to make keyboard listener");
+    let keyboard_listener = KeyboardListenerBuilder::new()
+        .text_input(true)
+        .attatch(&window)
+        .expect("failed to make keyboard listener");
     dbg!(&keyboard_listener);
 
     std::thread::spawn(move || {
@@ -23,6 +25,23 @@ fn main() {
             crosskey::Event::Release(key) => {
                 println!("release event!: {key}");
             },
+            crosskey::Event::ModifiersChanged(modifiers) => {
+                println!("modifiers changed!: {modifiers:?}");
+            },
+            crosskey::Event::LockChanged { lock, locked } => {
+                println!(
+                    "lock changed!: {lock:?} {}",
+                    if locked { "on" } else { "off" }
+                );
+            },
+            crosskey::Event::FocusGained => println!("focus gained!"),
+            crosskey::Event::FocusLost => println!("focus lost!"),
+            crosskey::Event::LayoutChanged(layout) => println!("layout changed!: {}", layout.name),
+            crosskey::Event::CompositionStart => println!("composition started!"),
+            crosskey::Event::CompositionUpdate { text, cursor } => {
+                println!("composing!: {text:?} {cursor:?}");
+            },
+            crosskey::Event::CompositionEnd(text) => println!("composition ended!: {text:?}"),
         }) {
             panic!("error while receiving: {e}")
         }
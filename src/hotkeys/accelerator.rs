@@ -0,0 +1,334 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{Code, Key, KeyEvent, Modifiers};
+
+// the modifiers an accelerator can hold, lock keys are ignored when matching
+const MODIFIERS: Modifiers = Modifiers::CONTROL
+    .union(Modifiers::ALT)
+    .union(Modifiers::ALT_GRAPH)
+    .union(Modifiers::SHIFT)
+    .union(Modifiers::SUPER)
+    .union(Modifiers::META);
+
+// the modifiers that choose which character of a key is typed
+const LEVEL_MODIFIERS: Modifiers = Modifiers::SHIFT.union(Modifiers::ALT_GRAPH);
+
+// the logo key sets both, so either name matches it
+pub(super) const LOGO: Modifiers = Modifiers::SUPER.union(Modifiers::META);
+
+/// A key combination such as `Ctrl+Shift+K`.
+///
+/// Parsed from modifier names followed by the key, separated by `+`:
+/// - modifiers: `Ctrl`/`Control`, `Alt`/`Option`, `AltGr`/`AltGraph`, `Shift` and
+///   `Super`/`Meta`/`Win`/`Cmd`, in any case
+/// - keys: a single character (`K`, `/`, `+`), a [`Key`] name (`Enter`, `ArrowUp`, `F5`) or a few
+///   common aliases (`Esc`, `Del`, `Up`, `Space`, `Plus`, ...). A character that needs shift or
+///   AltGr to be typed, such as `+` on a US layout, matches with them held whether or not they
+///   are written
+/// - physical keys: a [`Code`] name (`KeyK`, `Digit1`, `Numpad5`), which matches the same key on
+///   every layout
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Accelerator {
+    pub modifiers: Modifiers,
+    pub trigger: Trigger,
+}
+
+/// The non-modifier key of an [`Accelerator`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// The key produced by the layout, characters are compared without case since
+    /// [`KeyEvent::key`] has no modifiers applied. A character that isn't on the base level of
+    /// the key is compared with the character typed with shift and AltGr applied instead.
+    Key(Key),
+    /// The physical key, see: [`KeyEvent::code`]
+    Code(Code),
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AcceleratorError {
+    Empty,
+    /// The accelerator ends with a modifier, e.g. `Ctrl+`
+    MissingKey,
+    UnknownModifier(String),
+    UnknownKey(String),
+    /// The key is several characters that can't be a key name, e.g. `Ctrl+ÄÖ`, which no single
+    /// key press types. Ones that could be, e.g. `Ctrl+AB`, are an `UnknownKey` instead
+    MultipleCharacters(String),
+}
+
+impl Display for AcceleratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AcceleratorError::Empty => write!(f, "empty accelerator"),
+            AcceleratorError::MissingKey => write!(f, "accelerator has no key after the modifiers"),
+            AcceleratorError::UnknownModifier(m) => write!(f, "unknown modifier: {m:?}"),
+            AcceleratorError::UnknownKey(k) => write!(f, "unknown key: {k:?}"),
+            AcceleratorError::MultipleCharacters(k) => {
+                write!(f, "key is more than one character: {k:?}")
+            },
+        }
+    }
+}
+
+impl Accelerator {
+    pub fn new(modifiers: Modifiers, trigger: Trigger) -> Self {
+        Self {
+            modifiers: normalize_modifiers(modifiers),
+            trigger,
+        }
+    }
+
+    /// Whether `key` is this combination, extra modifiers other than the lock keys don't match.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let modifiers = normalize_modifiers(key.modifiers);
+        let same_modifiers = modifiers == normalize_modifiers(self.modifiers);
+
+        match &self.trigger {
+            Trigger::Code(code) => same_modifiers && key.code == *code,
+            Trigger::Key(Key::Character(c)) => {
+                let c = c.to_lowercase();
+
+                match &key.key {
+                    Key::Character(k) if k.to_lowercase() == c => same_modifiers,
+                    // e.g. `Ctrl++` is ctrl+shift+`=` on a US layout, shift being needed for `+`
+                    _ => {
+                        key.raw
+                            .level_char()
+                            .is_some_and(|level| level.to_lowercase().eq(c.chars()))
+                            && modifiers - LEVEL_MODIFIERS
+                                == normalize_modifiers(self.modifiers) - LEVEL_MODIFIERS
+                    },
+                }
+            },
+            Trigger::Key(k) => same_modifiers && key.key == *k,
+        }
+    }
+}
+
+impl FromStr for Accelerator {
+    type Err = AcceleratorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = split(s)?;
+
+        let modifiers = modifiers
+            .split('+')
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .try_fold(Modifiers::empty(), |all, m| {
+                parse_modifier(m)
+                    .map(|m| all | m)
+                    .ok_or_else(|| AcceleratorError::UnknownModifier(m.to_string()))
+            })?;
+
+        Ok(Self::new(modifiers, parse_trigger(key)?))
+    }
+}
+
+impl Display for Accelerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Modifiers::CONTROL, "Ctrl"),
+            (Modifiers::ALT, "Alt"),
+            (Modifiers::ALT_GRAPH, "AltGr"),
+            (Modifiers::SHIFT, "Shift"),
+            (LOGO, "Super"),
+        ];
+
+        for (modifier, name) in names {
+            if self.modifiers.intersects(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+
+        match &self.trigger {
+            Trigger::Key(Key::Character(c)) if c == " " => write!(f, "Space"),
+            Trigger::Key(Key::Character(c)) => {
+                let upper = c.to_uppercase();
+
+                // e.g. `ß` is `SS` in upper case, which would be parsed as another key
+                if upper.graphemes(true).count() == 1 {
+                    write!(f, "{upper}")
+                } else {
+                    write!(f, "{c}")
+                }
+            },
+            Trigger::Key(key) => write!(f, "{key}"),
+            Trigger::Code(code) => write!(f, "{code}"),
+        }
+    }
+}
+
+// written as the string they are parsed from
+#[cfg(feature = "serde")]
+impl serde::Serialize for Accelerator {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Accelerator {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// splits an accelerator into its modifiers (still separated by `+`) and its key
+pub(super) fn split(s: &str) -> Result<(&str, &str), AcceleratorError> {
+    let s = s.trim();
+
+    if s.is_empty() {
+        return Err(AcceleratorError::Empty);
+    }
+
+    // `+` is the separator, but it is also a key when it comes last (`Ctrl++`)
+    let (modifiers, key) = match s.rsplit_once('+') {
+        Some((rest, key)) if key.trim().is_empty() => match rest.trim_end().strip_suffix('+') {
+            Some(modifiers) => (modifiers, "+"),
+            None if rest.trim().is_empty() => ("", "+"),
+            None => return Err(AcceleratorError::MissingKey),
+        },
+        Some((modifiers, key)) => (modifiers, key),
+        None => ("", s),
+    };
+
+    Ok((modifiers, key.trim()))
+}
+
+fn normalize_modifiers(modifiers: Modifiers) -> Modifiers {
+    let modifiers = modifiers & MODIFIERS;
+
+    if modifiers.intersects(LOGO) {
+        modifiers | LOGO
+    } else {
+        modifiers
+    }
+}
+
+pub(super) fn parse_modifier(name: &str) -> Option<Modifiers> {
+    let modifier = match name.to_lowercase().as_str() {
+        "ctrl" | "control" => Modifiers::CONTROL,
+        "alt" | "option" => Modifiers::ALT,
+        "altgr" | "altgraph" => Modifiers::ALT_GRAPH,
+        "shift" => Modifiers::SHIFT,
+        "super" | "meta" | "win" | "windows" | "cmd" | "command" | "logo" => LOGO,
+        _ => return None,
+    };
+
+    Some(modifier)
+}
+
+pub(super) fn parse_trigger(name: &str) -> Result<Trigger, AcceleratorError> {
+    // a character may be several code points, e.g. `é` written as `e` and a combining accent
+    if name.graphemes(true).count() == 1 {
+        return Ok(Trigger::Key(Key::Character(name.to_lowercase())));
+    }
+
+    match Key::from_str(name) {
+        // any string that isn't a key name, e.g. `ÄÖ`
+        Ok(Key::Character(_)) => {
+            return Err(AcceleratorError::MultipleCharacters(name.to_string()))
+        },
+        Ok(key) => return Ok(Trigger::Key(key)),
+        Err(_) => (),
+    }
+
+    let key = match name.to_lowercase().as_str() {
+        "space" => Key::Character(" ".to_string()),
+        "plus" => Key::Character("+".to_string()),
+        "esc" | "escape" => Key::Escape,
+        "enter" | "return" => Key::Enter,
+        "tab" => Key::Tab,
+        "backspace" => Key::Backspace,
+        "del" | "delete" => Key::Delete,
+        "ins" | "insert" => Key::Insert,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pgup" | "pageup" => Key::PageUp,
+        "pgdn" | "pagedown" => Key::PageDown,
+        "up" | "arrowup" => Key::ArrowUp,
+        "down" | "arrowdown" => Key::ArrowDown,
+        "left" | "arrowleft" => Key::ArrowLeft,
+        "right" | "arrowright" => Key::ArrowRight,
+        // function keys in any case, e.g. `f5`
+        lower => match lower
+            .strip_prefix('f')
+            .and_then(|n| Key::from_str(&format!("F{n}")).ok())
+        {
+            Some(key) if !matches!(key, Key::Character(_)) => key,
+            // codes are only checked last so `Space` is the key rather than `Code::Space`
+            _ => {
+                return Code::from_str(name)
+                    .map(Trigger::Code)
+                    .map_err(|_| AcceleratorError::UnknownKey(name.to_string()))
+            },
+        },
+    };
+
+    Ok(Trigger::Key(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_characters() {
+        let plus = Accelerator::new(Modifiers::CONTROL, Trigger::Key(Key::Character("+".into())));
+        assert_eq!("Ctrl++".parse(), Ok(plus.clone()));
+        assert_eq!("Ctrl+Plus".parse(), Ok(plus));
+
+        assert_eq!(
+            "Ctrl+K".parse::<Accelerator>().map(|a| a.trigger),
+            Ok(Trigger::Key(Key::Character("k".into())))
+        );
+        // one character of two code points
+        assert_eq!(
+            "Ctrl+e\u{301}".parse::<Accelerator>().map(|a| a.trigger),
+            Ok(Trigger::Key(Key::Character("e\u{301}".into())))
+        );
+
+        assert_eq!(
+            "Ctrl+ÄÖ".parse::<Accelerator>(),
+            Err(AcceleratorError::MultipleCharacters("ÄÖ".into()))
+        );
+        assert_eq!(
+            "Ctrl+AB".parse::<Accelerator>(),
+            Err(AcceleratorError::UnknownKey("AB".into()))
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for accelerator in [
+            "Ctrl+K",
+            "Ctrl+Shift+ß",
+            "Alt+é",
+            "Alt+e\u{301}",
+            "Ctrl+ж",
+            "AltGr+ǉ",
+            "Ctrl++",
+            "Ctrl+Space",
+            "Super+Enter",
+            "Ctrl+KeyK",
+        ] {
+            let parsed: Accelerator = accelerator.parse().unwrap();
+            assert_eq!(parsed.to_string().parse(), Ok(parsed), "{accelerator}");
+        }
+
+        assert_eq!(
+            "ctrl+ß".parse::<Accelerator>().unwrap().to_string(),
+            "Ctrl+ß"
+        );
+        assert_eq!(
+            "ctrl+ä".parse::<Accelerator>().unwrap().to_string(),
+            "Ctrl+Ä"
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{KeySequence, Keymap};
+
+/// Key bindings read from, or written to, a config file.
+///
+/// Each binding maps a [`KeySequence`] to an action, which is a string unless another type is
+/// given. With the `config` feature they are read and written as TOML:
+/// ```toml
+/// "Ctrl+S" = "save"
+/// "Ctrl+X Ctrl+C" = "quit"
+/// ```
+/// Or JSON:
+/// ```json
+/// { "Ctrl+S": "save", "Ctrl+X Ctrl+C": "quit" }
+/// ```
+/// Sequences are checked while reading, so a binding with an unknown key, or bound twice (e.g.
+/// `ctrl+s` and `Ctrl+S`), is reported along with where it is in the file.
+///
+/// To act on them, convert them into a [`Keymap`] and [`feed`](Keymap::feed) it the events of a
+/// [`KeyboardListener`](crate::KeyboardListener). Unlike [`HotkeyListener`](super::HotkeyListener),
+/// which only takes single accelerators, it also matches sequences of several keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bindings<A = String> {
+    // in the order they were read or inserted
+    bindings: Vec<(KeySequence, A)>,
+}
+
+#[cfg(feature = "config")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The line and column of the error, both starting from 1, if it is in a file being read.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+#[cfg(feature = "config")]
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "line {line}, column {column}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl<A> Bindings<A> {
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Returns the action previously bound to `sequence`, if any.
+    pub fn insert(&mut self, sequence: KeySequence, action: A) -> Option<A> {
+        match self.bindings.iter_mut().find(|(s, _)| *s == sequence) {
+            Some((_, bound)) => Some(std::mem::replace(bound, action)),
+            None => {
+                self.bindings.push((sequence, action));
+                None
+            },
+        }
+    }
+
+    pub fn get(&self, sequence: &KeySequence) -> Option<&A> {
+        self.bindings
+            .iter()
+            .find(|(s, _)| s == sequence)
+            .map(|(_, action)| action)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&KeySequence, &A)> {
+        self.bindings
+            .iter()
+            .map(|(sequence, action)| (sequence, action))
+    }
+
+    // parses `key`, a sequence as written in the file, which mustn't be bound already
+    fn unbound_sequence(&self, key: &str) -> Result<KeySequence, String> {
+        let sequence: KeySequence = key.parse().map_err(|e| format!("{key:?}: {e}"))?;
+
+        // e.g. `ctrl+s` and `Ctrl+S`, which the format itself can't catch
+        if self.get(&sequence).is_some() {
+            return Err(format!(
+                "duplicate binding: {key:?} is {sequence}, which is already bound"
+            ));
+        }
+
+        Ok(sequence)
+    }
+}
+
+#[cfg(feature = "config")]
+impl<A: de::DeserializeOwned> Bindings<A> {
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        // read with the span of each key, as errors about a key are otherwise reported at the
+        // start of its table
+        let entries: Entries<toml::Spanned<String>, A> =
+            toml::from_str(s).map_err(|e| ConfigError {
+                position: e.span().map(|span| position(s, span.start)),
+                message: e.message().to_string(),
+            })?;
+
+        let mut bindings = Bindings::new();
+
+        for (key, action) in entries.0 {
+            let sequence = bindings
+                .unbound_sequence(key.get_ref())
+                .map_err(|message| ConfigError {
+                    position: Some(position(s, key.span().start)),
+                    message,
+                })?;
+
+            bindings.bindings.push((sequence, action));
+        }
+
+        Ok(bindings)
+    }
+}
+
+#[cfg(feature = "config")]
+impl<A: de::DeserializeOwned> Bindings<A> {
+    pub fn from_json(s: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(s).map_err(json_error)
+    }
+}
+
+#[cfg(feature = "config")]
+impl<A: Serialize> Bindings<A> {
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string(self).map_err(|e| ConfigError {
+            position: None,
+            message: e.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "config")]
+impl<A: Serialize> Bindings<A> {
+    pub fn to_json(&self) -> Result<String, ConfigError> {
+        serde_json::to_string_pretty(self).map_err(json_error)
+    }
+}
+
+impl<A> Default for Bindings<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Clone> From<&Keymap<A>> for Bindings<A> {
+    fn from(keymap: &Keymap<A>) -> Self {
+        Self {
+            bindings: keymap
+                .iter()
+                .map(|(sequence, action)| (sequence, action.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl<A> From<Bindings<A>> for Keymap<A> {
+    fn from(bindings: Bindings<A>) -> Self {
+        let mut keymap = Keymap::new();
+
+        for (sequence, action) in bindings.bindings {
+            keymap.bind_sequence(sequence, action);
+        }
+
+        keymap
+    }
+}
+
+impl<A: Serialize> Serialize for Bindings<A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.bindings.len()))?;
+
+        for (sequence, action) in &self.bindings {
+            map.serialize_entry(sequence, action)?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de, A: Deserialize<'de>> Deserialize<'de> for Bindings<A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BindingsVisitor<A>(PhantomData<A>);
+
+        impl<'de, A: Deserialize<'de>> Visitor<'de> for BindingsVisitor<A> {
+            type Value = Bindings<A>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map of key sequences to actions")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+                let mut bindings = Bindings::new();
+
+                // checked before reading the action so the error is reported at the key
+                while let Some(key) = map.next_key::<String>()? {
+                    let sequence = bindings.unbound_sequence(&key).map_err(de::Error::custom)?;
+
+                    bindings.bindings.push((sequence, map.next_value()?));
+                }
+
+                Ok(bindings)
+            }
+        }
+
+        deserializer.deserialize_map(BindingsVisitor(PhantomData))
+    }
+}
+
+#[cfg(feature = "config")]
+// the entries of a map in the order they were read
+struct Entries<K, A>(Vec<(K, A)>);
+
+#[cfg(feature = "config")]
+impl<'de, K: Deserialize<'de>, A: Deserialize<'de>> Deserialize<'de> for Entries<K, A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor<K, A>(PhantomData<(K, A)>);
+
+        impl<'de, K: Deserialize<'de>, A: Deserialize<'de>> Visitor<'de> for EntriesVisitor<K, A> {
+            type Value = Entries<K, A>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map of key sequences to actions")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+                let mut entries = Vec::new();
+
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor(PhantomData))
+    }
+}
+
+#[cfg(feature = "config")]
+fn json_error(e: serde_json::Error) -> ConfigError {
+    // 0 when the error isn't from reading a file
+    let position = (e.line() > 0).then(|| (e.line(), e.column()));
+
+    // the position is also appended to the message
+    let message = e.to_string();
+    let message = match position {
+        Some((line, column)) => message
+            .strip_suffix(&format!(" at line {line} column {column}"))
+            .unwrap_or(&message)
+            .to_string(),
+        None => message,
+    };
+
+    ConfigError { position, message }
+}
+
+#[cfg(feature = "config")]
+// the line and column of a byte offset into `s`
+fn position(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset.min(s.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(all(test, feature = "config"))]
+mod tests {
+    use super::*;
+
+    fn bindings() -> Bindings {
+        let mut bindings = Bindings::new();
+
+        for (sequence, action) in [
+            ("Ctrl+S", "save"),
+            ("Ctrl+X Ctrl+C", "quit"),
+            ("Alt+KeyK", "kill"),
+            ("Ctrl+Shift+ß", "sharp"),
+            ("g g", "top"),
+        ] {
+            bindings.insert(sequence.parse().unwrap(), action.to_string());
+        }
+
+        bindings
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let bindings = bindings();
+        let toml = bindings.to_toml().expect("failed to write bindings");
+
+        assert_eq!(Bindings::from_toml(&toml), Ok(bindings));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let bindings = bindings();
+        let json = bindings.to_json().expect("failed to write bindings");
+
+        assert_eq!(Bindings::from_json(&json), Ok(bindings));
+    }
+
+    #[test]
+    fn toml_errors() {
+        let bindings =
+            Bindings::<String>::from_toml("\"Ctrl+S\" = \"save\"\n\"Ctrl+Q\" = \"quit\"\n")
+                .expect("failed to read bindings");
+        assert_eq!(bindings.iter().count(), 2);
+
+        let error =
+            Bindings::<String>::from_toml("\"Ctrl+S\" = \"save\"\n  \"ctrl+s\" = \"quit\"\n")
+                .unwrap_err();
+        assert_eq!(error.position, Some((2, 3)));
+        assert!(error.message.contains("\"ctrl+s\""));
+        assert!(error.message.contains("Ctrl+S"));
+
+        let error =
+            Bindings::<String>::from_toml("\"Ctrl+S\" = \"save\"\n\"Ctrl+Nope\" = \"quit\"\n")
+                .unwrap_err();
+        assert_eq!(error.position, Some((2, 1)));
+    }
+
+    #[test]
+    fn json_errors() {
+        let error =
+            Bindings::<String>::from_json("{\n\"Ctrl+S\": \"save\",\n\"ctrl+s\": \"quit\"\n}")
+                .unwrap_err();
+        assert_eq!(error.position.map(|(line, _)| line), Some(3));
+        assert!(error.message.contains("\"ctrl+s\""));
+    }
+}
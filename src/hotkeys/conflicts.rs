@@ -0,0 +1,283 @@
+use std::borrow::Borrow;
+use std::fmt::{self, Display};
+
+use super::{Accelerator, KeySequence, Trigger};
+#[cfg(target_os = "linux")]
+use crate::Layout;
+use crate::{Code, Key, Modifiers};
+
+// the characters typed with shift on a US layout, and the key typing them
+const SHIFTED: [(char, char); 21] = [
+    ('!', '1'),
+    ('@', '2'),
+    ('#', '3'),
+    ('$', '4'),
+    ('%', '5'),
+    ('^', '6'),
+    ('&', '7'),
+    ('*', '8'),
+    ('(', '9'),
+    (')', '0'),
+    ('_', '-'),
+    ('+', '='),
+    ('{', '['),
+    ('}', ']'),
+    ('|', '\\'),
+    (':', ';'),
+    ('"', '\''),
+    ('<', ','),
+    ('>', '.'),
+    ('?', '/'),
+    ('~', '`'),
+];
+
+/// A problem with a set of bindings, see: [`find_conflicts`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Conflict {
+    /// The sequence is bound more than once.
+    Duplicate(KeySequence),
+    /// `prefix` matches as soon as it is pressed, so `sequence`, which starts with it, can never
+    /// be completed (see: [`SequenceMatcher`](super::SequenceMatcher)).
+    Shadowed {
+        prefix: KeySequence,
+        sequence: KeySequence,
+    },
+    /// A key of the sequence isn't on the layout, see: `find_unproducible` (Linux only)
+    Unproducible {
+        sequence: KeySequence,
+        accelerator: Accelerator,
+    },
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::Duplicate(sequence) => write!(f, "{sequence} is bound more than once"),
+            Conflict::Shadowed { prefix, sequence } => {
+                write!(f, "{prefix} shadows {sequence}")
+            },
+            Conflict::Unproducible {
+                sequence,
+                accelerator,
+            } if sequence.accelerators().len() > 1 => {
+                write!(
+                    f,
+                    "{accelerator} of {sequence} can't be pressed on the layout"
+                )
+            },
+            Conflict::Unproducible { sequence, .. } => {
+                write!(f, "{sequence} can't be pressed on the layout")
+            },
+        }
+    }
+}
+
+/// Finds sequences that are bound more than once and sequences shadowed by shorter ones, e.g.
+/// `Ctrl+X` shadowing `Ctrl+X Ctrl+S`.
+///
+/// Sequences written differently still conflict when they are pressed the same, e.g. `Ctrl+K` and
+/// `Ctrl+KeyK`, or `Ctrl++` and `Ctrl+Shift+=`. Which key types a character isn't known without a
+/// layout, so letter and digit codes and characters typed with shift are compared as they are on
+/// a US layout, which most layouts share them with.
+///
+/// Each duplicate is reported once, as the first sequence written differently from the one it
+/// duplicates, and a sequence shadowed by several prefixes is reported once for each of them.
+/// Meant for tests, so a set of default bindings can be checked in CI:
+/// ```
+/// # use crosskey::{find_conflicts, KeySequence};
+/// let bindings: Vec<KeySequence> = ["Ctrl+S", "Ctrl+X Ctrl+S", "Ctrl+X Ctrl+C"]
+///     .into_iter()
+///     .map(|s| s.parse().unwrap())
+///     .collect();
+///
+/// assert_eq!(find_conflicts(&bindings), vec![]);
+/// ```
+pub fn find_conflicts<I>(sequences: I) -> Vec<Conflict>
+where
+    I: IntoIterator,
+    I::Item: Borrow<KeySequence>,
+{
+    let sequences: Vec<I::Item> = sequences.into_iter().collect();
+    let mut conflicts = Vec::new();
+
+    // in the order they first appear, along with the sequence as written
+    let mut unique: Vec<(KeySequence, &KeySequence)> = Vec::new();
+    let mut duplicates: Vec<KeySequence> = Vec::new();
+
+    for sequence in &sequences {
+        let sequence = sequence.borrow();
+        let comparable = comparable_sequence(sequence);
+
+        if !unique.iter().any(|(s, _)| *s == comparable) {
+            unique.push((comparable, sequence));
+        } else if !duplicates.contains(&comparable) {
+            duplicates.push(comparable);
+            conflicts.push(Conflict::Duplicate(sequence.clone()));
+        }
+    }
+
+    for (prefix, prefix_written) in &unique {
+        for (sequence, sequence_written) in &unique {
+            if is_strict_prefix(prefix, sequence) {
+                conflicts.push(Conflict::Shadowed {
+                    prefix: (*prefix_written).clone(),
+                    sequence: (*sequence_written).clone(),
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+fn comparable_sequence(sequence: &KeySequence) -> KeySequence {
+    KeySequence::new(sequence.accelerators().iter().map(comparable).collect())
+        .expect("a sequence has at least one accelerator")
+}
+
+// the accelerator written the way all the others pressed the same are, see: `find_conflicts`
+fn comparable(accelerator: &Accelerator) -> Accelerator {
+    let mut modifiers = accelerator.modifiers;
+
+    let c = match &accelerator.trigger {
+        Trigger::Key(Key::Character(c)) => c.to_lowercase(),
+        Trigger::Code(Code::Space) => " ".to_string(),
+        Trigger::Code(code) => {
+            let name = code.to_string();
+
+            match name.strip_prefix("Key").or(name.strip_prefix("Digit")) {
+                Some(c) if c.len() == 1 => c.to_lowercase(),
+                _ => return Accelerator::new(modifiers, Trigger::Code(*code)),
+            }
+        },
+        Trigger::Key(key) => return Accelerator::new(modifiers, Trigger::Key(key.clone())),
+    };
+
+    let mut chars = c.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => match SHIFTED.iter().find(|(shifted, _)| *shifted == c) {
+            Some((_, key)) => {
+                modifiers |= Modifiers::SHIFT;
+                key.to_string()
+            },
+            None => c.to_string(),
+        },
+        _ => c,
+    };
+
+    Accelerator::new(modifiers, Trigger::Key(Key::Character(c)))
+}
+
+/// Finds the sequences with a key that isn't on `layout`, such as `Ctrl+ß` on a US layout.
+///
+/// A character is found on any level of a key, the same as it is matched, so `Ctrl+[` can be
+/// pressed on a German layout where `[` is `AltGr+8`.
+///
+/// Only on Linux, where [`Layout`] can load any installed layout by name.
+#[cfg(target_os = "linux")]
+pub fn find_unproducible<I>(sequences: I, layout: &Layout) -> Vec<Conflict>
+where
+    I: IntoIterator,
+    I::Item: Borrow<KeySequence>,
+{
+    sequences
+        .into_iter()
+        .filter_map(|sequence| {
+            let sequence = sequence.borrow();
+            let accelerator = sequence
+                .accelerators()
+                .iter()
+                .find(|a| !is_producible(a, layout))?;
+
+            Some(Conflict::Unproducible {
+                sequence: sequence.clone(),
+                accelerator: accelerator.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn is_producible(accelerator: &Accelerator, layout: &Layout) -> bool {
+    match &accelerator.trigger {
+        Trigger::Key(Key::Character(c)) => {
+            let mut chars = c.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => layout.find_key_for(c).is_some(),
+                // a character of several code points is only ever a key's base level
+                _ => layout.has_key(&Key::Character(c.clone())),
+            }
+        },
+        Trigger::Key(key) => layout.has_key(key),
+        Trigger::Code(code) => layout.has_code(*code),
+    }
+}
+
+fn is_strict_prefix(prefix: &KeySequence, sequence: &KeySequence) -> bool {
+    let (prefix, sequence) = (prefix.accelerators(), sequence.accelerators());
+
+    prefix.len() < sequence.len() && sequence.starts_with(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conflicts(sequences: &[&str]) -> Vec<Conflict> {
+        let sequences: Vec<KeySequence> = sequences.iter().map(|s| s.parse().unwrap()).collect();
+
+        find_conflicts(&sequences)
+    }
+
+    fn duplicate(sequence: &str) -> Conflict {
+        Conflict::Duplicate(sequence.parse().unwrap())
+    }
+
+    #[test]
+    fn duplicates() {
+        assert_eq!(conflicts(&["Ctrl+S", "Ctrl+X Ctrl+S"]), vec![]);
+        assert_eq!(conflicts(&["Ctrl+S", "ctrl+s"]), vec![duplicate("ctrl+s")]);
+
+        // the same key pressed, written another way
+        assert_eq!(
+            conflicts(&["Ctrl+K", "Ctrl+KeyK"]),
+            vec![duplicate("Ctrl+KeyK")]
+        );
+        assert_eq!(
+            conflicts(&["Ctrl+1", "Ctrl+Digit1"]),
+            vec![duplicate("Ctrl+Digit1")]
+        );
+        assert_eq!(
+            conflicts(&["Ctrl++", "Ctrl+Shift+="]),
+            vec![duplicate("Ctrl+Shift+=")]
+        );
+        assert_eq!(
+            conflicts(&["Ctrl++", "Ctrl+Shift+Plus"]),
+            vec![duplicate("Ctrl+Shift+Plus")]
+        );
+        let upper = KeySequence::from(Accelerator::new(
+            Modifiers::CONTROL | Modifiers::SHIFT,
+            Trigger::Key(Key::Character("A".to_string())),
+        ));
+        assert_eq!(
+            find_conflicts(["Ctrl+Shift+a".parse().unwrap(), upper.clone()]),
+            vec![Conflict::Duplicate(upper)]
+        );
+
+        // shift makes a letter another accelerator
+        assert_eq!(conflicts(&["Ctrl+Z", "Ctrl+Shift+Z"]), vec![]);
+        assert_eq!(conflicts(&["Ctrl+=", "Ctrl++"]), vec![]);
+    }
+
+    #[test]
+    fn shadowed() {
+        assert_eq!(
+            conflicts(&["Ctrl+KeyX", "Ctrl+X Ctrl+S"]),
+            vec![Conflict::Shadowed {
+                prefix: "Ctrl+KeyX".parse().unwrap(),
+                sequence: "Ctrl+X Ctrl+S".parse().unwrap(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use std::str::FromStr;
+
+use super::accelerator::{parse_modifier, parse_trigger, split, LOGO};
+use super::{Accelerator, AcceleratorError, KeySequence, Trigger};
+use crate::{Code, Key, Modifiers};
+
+// in the order macOS draws them. a mac has no alt graph, so it gets the alternative key symbol
+// of other keyboards rather than option, which is alt
+const MAC_MODIFIERS: [(Modifiers, char); 5] = [
+    (Modifiers::CONTROL, '⌃'),
+    (Modifiers::ALT, '⌥'),
+    (Modifiers::ALT_GRAPH, '⎇'),
+    (Modifiers::SHIFT, '⇧'),
+    (LOGO, '⌘'),
+];
+
+const MAC_KEYS: [(Key, &str); 13] = [
+    (Key::Enter, "↩"),
+    (Key::Tab, "⇥"),
+    (Key::Backspace, "⌫"),
+    (Key::Delete, "⌦"),
+    (Key::Escape, "⎋"),
+    (Key::ArrowUp, "↑"),
+    (Key::ArrowDown, "↓"),
+    (Key::ArrowLeft, "←"),
+    (Key::ArrowRight, "→"),
+    (Key::PageUp, "⇞"),
+    (Key::PageDown, "⇟"),
+    (Key::Home, "↖"),
+    (Key::End, "↘"),
+];
+
+/// How an [`AcceleratorFormat`] writes accelerators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AcceleratorStyle {
+    /// Symbols without separators, e.g. `⌃⌥⇧⌘K`. Alt graph, which a mac doesn't have, is
+    /// written as `⎇`.
+    Mac,
+    /// e.g. `Ctrl+Alt+Shift+Win+K`
+    Windows,
+    /// e.g. `Ctrl+Alt+Shift+Super+K`
+    Linux,
+}
+
+impl AcceleratorStyle {
+    /// The style of the platform being compiled for.
+    pub fn native() -> Self {
+        if cfg!(target_os = "macos") {
+            AcceleratorStyle::Mac
+        } else if cfg!(target_os = "windows") {
+            AcceleratorStyle::Windows
+        } else {
+            AcceleratorStyle::Linux
+        }
+    }
+}
+
+/// The names an [`AcceleratorFormat`] gives modifiers and keys, so accelerators can be shown in
+/// the language of the application.
+///
+/// Keys without a name are written the same as [`Accelerator`]'s `Display`, e.g. `F5` or `K`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyNames {
+    modifiers: Vec<(Modifiers, String)>,
+    keys: Vec<(Key, String)>,
+}
+
+impl KeyNames {
+    pub fn english() -> Self {
+        Self::new(
+            ["Ctrl", "Alt", "AltGr", "Shift"],
+            [
+                "Space",
+                "Enter",
+                "Tab",
+                "Backspace",
+                "Del",
+                "Ins",
+                "Esc",
+                "Home",
+                "End",
+                "PgUp",
+                "PgDn",
+                "Up",
+                "Down",
+                "Left",
+                "Right",
+            ],
+        )
+    }
+
+    pub fn german() -> Self {
+        Self::new(
+            ["Strg", "Alt", "AltGr", "Umschalt"],
+            [
+                "Leertaste",
+                "Eingabe",
+                "Tab",
+                "Rücktaste",
+                "Entf",
+                "Einfg",
+                "Esc",
+                "Pos1",
+                "Ende",
+                "Bild auf",
+                "Bild ab",
+                "Nach-oben",
+                "Nach-unten",
+                "Nach-links",
+                "Nach-rechts",
+            ],
+        )
+    }
+
+    pub fn french() -> Self {
+        Self::new(
+            ["Ctrl", "Alt", "AltGr", "Maj"],
+            [
+                "Espace",
+                "Entrée",
+                "Tab",
+                "Retour arrière",
+                "Suppr",
+                "Inser",
+                "Échap",
+                "Origine",
+                "Fin",
+                "Pg préc",
+                "Pg suiv",
+                "Haut",
+                "Bas",
+                "Gauche",
+                "Droite",
+            ],
+        )
+    }
+
+    /// Names `modifier`, which should be a single modifier, either of `SUPER` and `META` names
+    /// the logo key (`Win` or `Super` by default).
+    pub fn set_modifier(&mut self, modifier: Modifiers, name: impl Into<String>) {
+        let modifier = if modifier.intersects(LOGO) {
+            LOGO
+        } else {
+            modifier
+        };
+
+        self.modifiers.retain(|(m, _)| *m != modifier);
+        self.modifiers.push((modifier, name.into()));
+    }
+
+    /// Names `key`, a character key is named without case (e.g. `ä` for `Ä`).
+    pub fn set_key(&mut self, key: Key, name: impl Into<String>) {
+        let key = lowercase(key);
+
+        self.keys.retain(|(k, _)| *k != key);
+        self.keys.push((key, name.into()));
+    }
+
+    fn new(modifiers: [&str; 4], keys: [&str; 15]) -> Self {
+        let modifiers = [
+            Modifiers::CONTROL,
+            Modifiers::ALT,
+            Modifiers::ALT_GRAPH,
+            Modifiers::SHIFT,
+        ]
+        .into_iter()
+        .zip(modifiers)
+        .map(|(modifier, name)| (modifier, name.to_string()))
+        .collect();
+
+        let keys = [
+            Key::Character(" ".to_string()),
+            Key::Enter,
+            Key::Tab,
+            Key::Backspace,
+            Key::Delete,
+            Key::Insert,
+            Key::Escape,
+            Key::Home,
+            Key::End,
+            Key::PageUp,
+            Key::PageDown,
+            Key::ArrowUp,
+            Key::ArrowDown,
+            Key::ArrowLeft,
+            Key::ArrowRight,
+        ]
+        .into_iter()
+        .zip(keys)
+        .map(|(key, name)| (key, name.to_string()))
+        .collect();
+
+        Self { modifiers, keys }
+    }
+
+    fn modifier(&self, modifier: Modifiers) -> Option<&str> {
+        self.modifiers
+            .iter()
+            .find(|(m, _)| *m == modifier)
+            .map(|(_, name)| name.as_str())
+    }
+
+    fn key(&self, key: &Key) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+impl Default for KeyNames {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+/// Writes accelerators the way the platform shows them in menus and tooltips, and reads them
+/// back.
+///
+/// Unlike [`Accelerator`]'s `Display`, which is for config files, the output depends on the
+/// style and names:
+/// ```
+/// # use crosskey::{Accelerator, AcceleratorFormat, AcceleratorStyle, KeyNames};
+/// let accelerator: Accelerator = "Ctrl+Alt+Shift+Cmd+K".parse().unwrap();
+///
+/// let mac = AcceleratorFormat::new(AcceleratorStyle::Mac);
+/// assert_eq!(mac.format(&accelerator), "⌃⌥⇧⌘K");
+///
+/// let windows = AcceleratorFormat::new(AcceleratorStyle::Windows).with_names(KeyNames::german());
+/// assert_eq!(windows.format(&accelerator), "Strg+Alt+Umschalt+Win+K");
+/// assert_eq!(windows.parse("Strg+Alt+Umschalt+Win+K"), Ok(accelerator));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AcceleratorFormat {
+    style: AcceleratorStyle,
+    names: KeyNames,
+}
+
+impl AcceleratorFormat {
+    /// Uses English names, see: [`AcceleratorFormat::with_names`]
+    pub fn new(style: AcceleratorStyle) -> Self {
+        Self {
+            style,
+            names: KeyNames::english(),
+        }
+    }
+
+    /// See: [`AcceleratorStyle::native`]
+    pub fn native() -> Self {
+        Self::new(AcceleratorStyle::native())
+    }
+
+    pub fn with_names(mut self, names: KeyNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    pub fn style(&self) -> AcceleratorStyle {
+        self.style
+    }
+
+    pub fn names(&self) -> &KeyNames {
+        &self.names
+    }
+
+    /// A [`Trigger::Code`] is written as the key it is on a US layout, e.g. `Ctrl+K` for
+    /// `Ctrl+KeyK`, so it is read back as that key rather than the code.
+    pub fn format(&self, accelerator: &Accelerator) -> String {
+        let mut s = String::new();
+
+        if self.style == AcceleratorStyle::Mac {
+            for (modifier, symbol) in MAC_MODIFIERS {
+                if accelerator.modifiers.intersects(modifier) {
+                    s.push(symbol);
+                }
+            }
+        } else {
+            for (modifier, name) in self.text_modifiers() {
+                if accelerator.modifiers.intersects(modifier) {
+                    s.push_str(name);
+                    s.push('+');
+                }
+            }
+        }
+
+        s.push_str(&self.key_name(&accelerator.trigger));
+        s
+    }
+
+    /// The accelerators are separated by `, `, e.g. `Ctrl+K, Ctrl+C`.
+    pub fn format_sequence(&self, sequence: &KeySequence) -> String {
+        sequence
+            .accelerators()
+            .iter()
+            .map(|accelerator| self.format(accelerator))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Reads an accelerator written by [`AcceleratorFormat::format`], names are compared without
+    /// case.
+    ///
+    /// Anything [`Accelerator`] parses from is also accepted, so English names can still be read
+    /// when other names are used.
+    pub fn parse(&self, s: &str) -> Result<Accelerator, AcceleratorError> {
+        let symbols = MAC_MODIFIERS
+            .iter()
+            .any(|(_, symbol)| s.trim_start().starts_with(*symbol));
+
+        // a mac accelerator without modifiers is read the same as the others
+        if self.style == AcceleratorStyle::Mac && symbols {
+            self.parse_mac(s)
+        } else {
+            self.parse_text(s)
+        }
+    }
+
+    /// Reads a sequence written by [`AcceleratorFormat::format_sequence`].
+    pub fn parse_sequence(&self, s: &str) -> Result<KeySequence, AcceleratorError> {
+        let accelerators = s
+            .split(", ")
+            .map(|accelerator| self.parse(accelerator))
+            .collect::<Result<_, _>>()?;
+
+        KeySequence::new(accelerators).ok_or(AcceleratorError::Empty)
+    }
+
+    // the names of the modifiers in the order they are written
+    fn text_modifiers(&self) -> [(Modifiers, &str); 5] {
+        let logo = match self.style {
+            AcceleratorStyle::Windows => "Win",
+            _ => "Super",
+        };
+
+        [
+            (Modifiers::CONTROL, "Ctrl"),
+            (Modifiers::ALT, "Alt"),
+            (Modifiers::ALT_GRAPH, "AltGr"),
+            (Modifiers::SHIFT, "Shift"),
+            (LOGO, logo),
+        ]
+        .map(|(modifier, name)| (modifier, self.names.modifier(modifier).unwrap_or(name)))
+    }
+
+    fn key_name(&self, trigger: &Trigger) -> String {
+        let code_key;
+        let key = match trigger {
+            Trigger::Key(key) => key,
+            // named after the key on a US layout, which is read back as that key
+            Trigger::Code(code) => match us_key(*code) {
+                Some(key) => {
+                    code_key = key;
+                    &code_key
+                },
+                None => return code.to_string(),
+            },
+        };
+
+        let symbol = MAC_KEYS
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, symbol)| *symbol)
+            .filter(|_| self.style == AcceleratorStyle::Mac);
+
+        if let Some(name) = symbol.or_else(|| self.names.key(&lowercase(key.clone()))) {
+            return name.to_string();
+        }
+
+        // the same as `Display`, e.g. `ß` isn't written as `SS`
+        Accelerator::new(Modifiers::empty(), Trigger::Key(key.clone())).to_string()
+    }
+
+    // the leading modifier symbols and the key after them
+    fn parse_mac(&self, s: &str) -> Result<Accelerator, AcceleratorError> {
+        let mut modifiers = Modifiers::empty();
+        let mut key = s.trim();
+
+        while let Some((modifier, symbol)) = MAC_MODIFIERS
+            .iter()
+            .find(|(_, symbol)| key.starts_with(*symbol))
+        {
+            modifiers |= *modifier;
+            key = &key[symbol.len_utf8()..];
+        }
+
+        match key.trim() {
+            "" => Err(AcceleratorError::MissingKey),
+            key => Ok(Accelerator::new(modifiers, self.parse_trigger(key)?)),
+        }
+    }
+
+    fn parse_text(&self, s: &str) -> Result<Accelerator, AcceleratorError> {
+        let (modifiers, key) = split(s)?;
+
+        let modifiers = modifiers
+            .split('+')
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .try_fold(Modifiers::empty(), |all, m| {
+                self.parse_modifier(m)
+                    .map(|m| all | m)
+                    .ok_or_else(|| AcceleratorError::UnknownModifier(m.to_string()))
+            })?;
+
+        Ok(Accelerator::new(modifiers, self.parse_trigger(key)?))
+    }
+
+    fn parse_modifier(&self, name: &str) -> Option<Modifiers> {
+        let lower = name.to_lowercase();
+
+        self.text_modifiers()
+            .into_iter()
+            .find(|(_, n)| n.to_lowercase() == lower)
+            .map(|(modifier, _)| modifier)
+            .or_else(|| parse_modifier(name))
+    }
+
+    fn parse_trigger(&self, name: &str) -> Result<Trigger, AcceleratorError> {
+        let lower = name.to_lowercase();
+
+        let symbol = MAC_KEYS
+            .iter()
+            .find(|(_, symbol)| *symbol == name)
+            .map(|(key, _)| key.clone())
+            .filter(|_| self.style == AcceleratorStyle::Mac);
+
+        let named = || {
+            self.names
+                .keys
+                .iter()
+                .find(|(_, n)| n.to_lowercase() == lower)
+                .map(|(key, _)| key.clone())
+        };
+
+        match symbol.or_else(named) {
+            Some(key) => Ok(Trigger::Key(key)),
+            None => parse_trigger(name),
+        }
+    }
+}
+
+impl Default for AcceleratorFormat {
+    fn default() -> Self {
+        Self::native()
+    }
+}
+
+// the key at `code` on a US layout, `None` for keys that are only told apart by their code,
+// e.g. `Numpad5` and `ShiftLeft`
+fn us_key(code: Code) -> Option<Key> {
+    let c = match code {
+        Code::Backquote => '`',
+        Code::Minus => '-',
+        Code::Equal => '=',
+        Code::BracketLeft => '[',
+        Code::BracketRight => ']',
+        Code::Backslash => '\\',
+        Code::Semicolon => ';',
+        Code::Quote => '\'',
+        Code::Comma => ',',
+        Code::Period => '.',
+        Code::Slash => '/',
+        Code::Space => ' ',
+        code => {
+            let name = code.to_string();
+
+            return match name.strip_prefix("Key").or(name.strip_prefix("Digit")) {
+                Some(c) if c.len() == 1 => Some(Key::Character(c.to_lowercase())),
+                // e.g. `Enter` and `F5`, which are named the same as their key
+                _ => Key::from_str(&name)
+                    .ok()
+                    .filter(|key| !matches!(key, Key::Character(_))),
+            };
+        },
+    };
+
+    Some(Key::Character(c.to_string()))
+}
+
+// characters are stored without case, the same as in an accelerator
+fn lowercase(key: Key) -> Key {
+    match key {
+        Key::Character(c) => Key::Character(c.to_lowercase()),
+        key => key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let modifiers = [
+            Modifiers::CONTROL,
+            Modifiers::ALT,
+            Modifiers::ALT_GRAPH,
+            Modifiers::SHIFT,
+            LOGO,
+        ];
+        let every = modifiers
+            .into_iter()
+            .fold(Modifiers::empty(), |all, m| all | m);
+
+        let styles = [
+            AcceleratorStyle::Mac,
+            AcceleratorStyle::Windows,
+            AcceleratorStyle::Linux,
+        ];
+
+        for style in styles {
+            for names in [KeyNames::english(), KeyNames::german(), KeyNames::french()] {
+                let format = AcceleratorFormat::new(style).with_names(names);
+
+                for modifiers in modifiers.into_iter().chain([Modifiers::empty(), every]) {
+                    for key in [Key::Character("k".to_string()), Key::Enter] {
+                        let accelerator = Accelerator::new(modifiers, Trigger::Key(key));
+                        let formatted = format.format(&accelerator);
+
+                        assert_eq!(format.parse(&formatted), Ok(accelerator), "{formatted}");
+                    }
+
+                    // codes are named after their key, and read back as it
+                    for (code, key) in [
+                        (Code::KeyK, Some(Key::Character("k".to_string()))),
+                        (Code::Digit1, Some(Key::Character("1".to_string()))),
+                        (Code::Slash, Some(Key::Character("/".to_string()))),
+                        (Code::Enter, Some(Key::Enter)),
+                        (Code::Numpad5, None),
+                    ] {
+                        let accelerator = Accelerator::new(modifiers, Trigger::Code(code));
+                        let formatted = format.format(&accelerator);
+                        let read = Accelerator::new(
+                            modifiers,
+                            key.map_or(Trigger::Code(code), Trigger::Key),
+                        );
+
+                        assert_eq!(format.parse(&formatted), Ok(read), "{formatted}");
+                    }
+                }
+
+                let sequence: KeySequence = "Ctrl+X Ctrl+Shift+S g Alt+Enter".parse().unwrap();
+                let formatted = format.format_sequence(&sequence);
+                assert_eq!(
+                    format.parse_sequence(&formatted),
+                    Ok(sequence),
+                    "{formatted}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn code_names() {
+        let windows = AcceleratorFormat::new(AcceleratorStyle::Windows);
+        let mac = AcceleratorFormat::new(AcceleratorStyle::Mac);
+        let format = |format: &AcceleratorFormat, s: &str| format.format(&s.parse().unwrap());
+
+        assert_eq!(format(&windows, "Ctrl+KeyK"), "Ctrl+K");
+        assert_eq!(format(&windows, "Ctrl+Digit1"), "Ctrl+1");
+        assert_eq!(format(&windows, "Ctrl+Quote"), "Ctrl+'");
+        assert_eq!(
+            windows.format_sequence(&"Ctrl+KeyK Ctrl+ß".parse().unwrap()),
+            "Ctrl+K, Ctrl+ß"
+        );
+        assert_eq!(format(&windows, "Ctrl+ArrowUp"), "Ctrl+Up");
+        assert_eq!(format(&windows, "Ctrl+Numpad5"), "Ctrl+Numpad5");
+        assert_eq!(format(&mac, "Cmd+KeyK"), "⌘K");
+        assert_eq!(format(&mac, "Cmd+Backquote"), "⌘`");
+    }
+}
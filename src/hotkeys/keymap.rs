@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+
+use super::{Accelerator, AcceleratorError, KeySequence, SequenceMatch, SequenceMatcher};
+use crate::Event;
+
+/// Bindings from [`KeySequence`]s to user defined actions.
+#[derive(Debug)]
+pub struct Keymap<A> {
+    matcher: SequenceMatcher<A>,
+    fallthrough: bool,
+}
+
+impl<A> Keymap<A> {
+    pub fn new() -> Self {
+        Self {
+            matcher: SequenceMatcher::new(),
+            fallthrough: true,
+        }
+    }
+
+    /// Parses `sequence` (see: [`KeySequence`]) and binds it to `action`, returns the action
+    /// previously bound to it, if any.
+    pub fn bind(&mut self, sequence: &str, action: A) -> Result<Option<A>, AcceleratorError> {
+        Ok(self.bind_sequence(sequence.parse()?, action))
+    }
+
+    /// See: [`Keymap::bind`]
+    pub fn bind_sequence(&mut self, sequence: KeySequence, action: A) -> Option<A> {
+        self.matcher.insert(sequence, action)
+    }
+
+    pub fn unbind(&mut self, sequence: &KeySequence) -> Option<A> {
+        self.matcher.remove(sequence)
+    }
+
+    /// Whether keys that aren't bound are passed on to the keymap below this one in a
+    /// [`KeymapStack`], which they are by default.
+    pub fn set_fallthrough(&mut self, fallthrough: bool) {
+        self.fallthrough = fallthrough;
+    }
+
+    /// See: [`SequenceMatcher::set_timeout`]
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.matcher.set_timeout(timeout);
+    }
+
+    /// See: [`SequenceMatcher::pending`]
+    pub fn pending(&self) -> &[Accelerator] {
+        self.matcher.pending()
+    }
+
+    /// See: [`SequenceMatcher::iter`]
+    pub fn iter(&self) -> impl Iterator<Item = (KeySequence, &A)> {
+        self.matcher.iter()
+    }
+
+    /// See: [`SequenceMatcher::feed`]
+    pub fn feed(&mut self, event: &Event) -> SequenceMatch<'_, A> {
+        self.matcher.feed(event)
+    }
+}
+
+impl<A> Default for Keymap<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a [`KeymapStack`] changes modes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ModeSwitch<M> {
+    /// Replaces the top mode, e.g. going from normal to insert mode
+    Set(M),
+    /// Layers a mode on top of the others, e.g. for a one-off window management mode
+    Push(M),
+    /// Removes the top mode, unless it is the only one
+    Pop,
+}
+
+/// Implemented by the actions of a [`KeymapStack`] so bindings can switch modes.
+pub trait ModeAction<M> {
+    /// The switch applied when the action is matched, before it is returned.
+    fn mode_switch(&self) -> Option<ModeSwitch<M>>;
+}
+
+/// Modes (e.g. normal, insert and visual) each with a [`Keymap`] of their own, stacked on top
+/// of each other.
+///
+/// Keys go to the keymap of the top mode first, and fall through to the modes below while they
+/// aren't bound (see: [`Keymap::set_fallthrough`]). Once a key starts a sequence, the following
+/// keys go to the same keymap until it matches or is aborted.
+#[derive(Debug)]
+pub struct KeymapStack<M, A> {
+    keymaps: HashMap<M, Keymap<A>>,
+    // from the bottom to the top, never empty
+    modes: Vec<M>,
+}
+
+impl<M: Clone + Eq + Hash, A> KeymapStack<M, A> {
+    pub fn new(mode: M) -> Self {
+        Self {
+            keymaps: HashMap::new(),
+            modes: vec![mode],
+        }
+    }
+
+    /// Sets the keymap of `mode`, returns the previous one, if any.
+    ///
+    /// A mode without a keymap doesn't bind any keys.
+    pub fn insert(&mut self, mode: M, keymap: Keymap<A>) -> Option<Keymap<A>> {
+        self.keymaps.insert(mode, keymap)
+    }
+
+    pub fn keymap_mut(&mut self, mode: &M) -> Option<&mut Keymap<A>> {
+        self.keymaps.get_mut(mode)
+    }
+
+    /// The top mode.
+    pub fn mode(&self) -> &M {
+        self.modes.last().unwrap()
+    }
+
+    /// Every mode from the bottom to the top.
+    pub fn modes(&self) -> &[M] {
+        &self.modes
+    }
+
+    /// Drops the keys pressed so far, since they were pressed in another mode.
+    pub fn switch_mode(&mut self, switch: ModeSwitch<M>) {
+        apply_switch(&mut self.modes, switch);
+
+        for keymap in self.keymaps.values_mut() {
+            keymap.matcher.reset();
+        }
+    }
+
+    /// The keys of the partially matched sequence, empty if there isn't one.
+    pub fn pending(&self) -> &[Accelerator] {
+        self.keymaps
+            .values()
+            .map(Keymap::pending)
+            .find(|pending| !pending.is_empty())
+            .unwrap_or_default()
+    }
+}
+
+impl<M: Clone + Eq + Hash, A: ModeAction<M>> KeymapStack<M, A> {
+    /// Feeds `event` to the keymap it goes to, switching modes if an action is matched.
+    pub fn feed(&mut self, event: &Event) -> SequenceMatch<'_, A> {
+        let Event::Press {
+            key,
+            repeat_count: 0,
+        } = event
+        else {
+            return SequenceMatch::NoMatch;
+        };
+
+        for keymap in self.keymaps.values_mut() {
+            keymap.matcher.check_timeout(key.timestamp);
+        }
+
+        // a partially matched sequence takes priority over the modes above it
+        let pending = self.modes.iter().find(|mode| {
+            self.keymaps
+                .get(mode)
+                .is_some_and(|k| !k.pending().is_empty())
+        });
+
+        let mode = pending.or_else(|| {
+            self.modes.iter().rev().find_map(|mode| {
+                let keymap = self.keymaps.get(mode)?;
+
+                if keymap.matcher.accepts(key) {
+                    Some(Some(mode))
+                } else if keymap.fallthrough {
+                    None
+                } else {
+                    Some(None)
+                }
+            })?
+        });
+
+        let Some(keymap) = mode.cloned().and_then(|mode| self.keymaps.get_mut(&mode)) else {
+            return SequenceMatch::NoMatch;
+        };
+
+        let result = keymap.feed(event);
+
+        if let SequenceMatch::Matched(action) = &result {
+            // the other keymaps can't have any keys pressed so far, so there's nothing to drop
+            if let Some(switch) = action.mode_switch() {
+                apply_switch(&mut self.modes, switch);
+            }
+        }
+
+        result
+    }
+}
+
+fn apply_switch<M>(modes: &mut Vec<M>, switch: ModeSwitch<M>) {
+    match switch {
+        ModeSwitch::Set(mode) => *modes.last_mut().unwrap() = mode,
+        ModeSwitch::Push(mode) => modes.push(mode),
+        ModeSwitch::Pop if modes.len() > 1 => {
+            modes.pop();
+        },
+        ModeSwitch::Pop => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Code, Key, KeyEvent, Modifiers};
+
+    #[derive(Debug, PartialEq)]
+    enum Action {
+        Save,
+        Insert,
+        Normal,
+        Window,
+        Back,
+    }
+
+    impl ModeAction<&'static str> for Action {
+        fn mode_switch(&self) -> Option<ModeSwitch<&'static str>> {
+            match self {
+                Action::Save => None,
+                Action::Insert => Some(ModeSwitch::Set("insert")),
+                Action::Normal => Some(ModeSwitch::Set("normal")),
+                Action::Window => Some(ModeSwitch::Push("window")),
+                Action::Back => Some(ModeSwitch::Pop),
+            }
+        }
+    }
+
+    fn keymap(bindings: Vec<(&str, Action)>) -> Keymap<Action> {
+        let mut keymap = Keymap::new();
+
+        for (sequence, action) in bindings {
+            keymap.bind(sequence, action).unwrap();
+        }
+
+        keymap
+    }
+
+    fn press(c: &str, modifiers: Modifiers) -> Event {
+        Event::Press {
+            key: KeyEvent::test(
+                Key::Character(c.to_string()),
+                Code::Unidentified,
+                0,
+                modifiers,
+            ),
+            repeat_count: 0,
+        }
+    }
+
+    fn stack() -> KeymapStack<&'static str, Action> {
+        let mut stack = KeymapStack::new("normal");
+        stack.insert(
+            "normal",
+            keymap(vec![
+                ("Ctrl+X Ctrl+S", Action::Save),
+                ("i", Action::Insert),
+                ("Ctrl+W", Action::Window),
+            ]),
+        );
+        stack.insert(
+            "insert",
+            keymap(vec![("Escape", Action::Normal), ("Ctrl+S", Action::Insert)]),
+        );
+        stack.insert("window", keymap(vec![("q", Action::Back)]));
+
+        stack
+    }
+
+    #[test]
+    fn pending_first() {
+        let mut stack = stack();
+        stack.switch_mode(ModeSwitch::Push("insert"));
+
+        // falls through to normal mode, which then gets `Ctrl+S` even though insert mode binds it
+        assert!(matches!(
+            stack.feed(&press("x", Modifiers::CONTROL)),
+            SequenceMatch::Partial(_)
+        ));
+        assert_eq!(stack.pending(), ["Ctrl+X".parse().unwrap()]);
+        assert_eq!(
+            stack.feed(&press("s", Modifiers::CONTROL)),
+            SequenceMatch::Matched(&Action::Save)
+        );
+
+        assert_eq!(
+            stack.feed(&press("s", Modifiers::CONTROL)),
+            SequenceMatch::Matched(&Action::Insert)
+        );
+    }
+
+    #[test]
+    fn fallthrough() {
+        let mut stack = stack();
+        stack.switch_mode(ModeSwitch::Push("window"));
+
+        assert!(matches!(
+            stack.feed(&press("x", Modifiers::CONTROL)),
+            SequenceMatch::Partial(_)
+        ));
+        stack.switch_mode(ModeSwitch::Pop);
+        stack.switch_mode(ModeSwitch::Push("window"));
+        assert!(stack.pending().is_empty());
+
+        stack.keymap_mut(&"window").unwrap().set_fallthrough(false);
+        assert_eq!(
+            stack.feed(&press("x", Modifiers::CONTROL)),
+            SequenceMatch::NoMatch
+        );
+        assert!(stack.pending().is_empty());
+
+        // a mode without a keymap doesn't stop keys
+        stack.switch_mode(ModeSwitch::Set("visual"));
+        assert_eq!(
+            stack.feed(&press("i", Modifiers::empty())),
+            SequenceMatch::Matched(&Action::Insert)
+        );
+        assert_eq!(stack.modes(), ["normal", "insert"]);
+    }
+
+    #[test]
+    fn mode_switches() {
+        let mut stack = stack();
+
+        assert_eq!(
+            stack.feed(&press("i", Modifiers::empty())),
+            SequenceMatch::Matched(&Action::Insert)
+        );
+        assert_eq!(stack.modes(), ["insert"]);
+
+        stack.switch_mode(ModeSwitch::Set("normal"));
+        assert_eq!(
+            stack.feed(&press("w", Modifiers::CONTROL)),
+            SequenceMatch::Matched(&Action::Window)
+        );
+        assert_eq!(stack.modes(), ["normal", "window"]);
+        assert_eq!(*stack.mode(), "window");
+
+        assert_eq!(
+            stack.feed(&press("q", Modifiers::empty())),
+            SequenceMatch::Matched(&Action::Back)
+        );
+        assert_eq!(stack.modes(), ["normal"]);
+
+        // the bottom mode stays
+        stack.switch_mode(ModeSwitch::Pop);
+        assert_eq!(stack.modes(), ["normal"]);
+        stack.switch_mode(ModeSwitch::Set("insert"));
+        assert_eq!(*stack.mode(), "insert");
+    }
+}
@@ -0,0 +1,161 @@
+mod accelerator;
+#[cfg(feature = "serde")]
+mod config;
+mod conflicts;
+mod format;
+mod keymap;
+mod sequence;
+
+use std::sync::{Arc, Mutex};
+use std::{fmt, thread};
+
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+
+pub use self::accelerator::{Accelerator, AcceleratorError, Trigger};
+#[cfg(feature = "serde")]
+pub use self::config::Bindings;
+#[cfg(feature = "config")]
+pub use self::config::ConfigError;
+#[cfg(target_os = "linux")]
+pub use self::conflicts::find_unproducible;
+pub use self::conflicts::{find_conflicts, Conflict};
+pub use self::format::{AcceleratorFormat, AcceleratorStyle, KeyNames};
+pub use self::keymap::{Keymap, KeymapStack, ModeAction, ModeSwitch};
+pub use self::sequence::{KeySequence, SequenceMatch, SequenceMatcher};
+use crate::{Event, KeyEvent, KeyboardListener, ListenerError, ListenerHandle};
+
+type Callback = Arc<dyn Fn(&KeyEvent) + Send + Sync>;
+
+/// Identifies a hotkey registered with [`HotkeyListener::register`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HotkeyId(u64);
+
+/// Calls a callback whenever its [`Accelerator`] is pressed in the attached window.
+///
+/// Callbacks are called from a thread of the listener's own, in the order they were registered.
+/// Holding the keys down doesn't call them again. It can share the window with other listeners,
+/// each of which still receives every event.
+#[derive(Clone)]
+pub struct HotkeyListener {
+    listener: KeyboardListener,
+    hotkeys: Arc<Mutex<Hotkeys>>,
+}
+
+impl HotkeyListener {
+    pub fn attatch<H: HasWindowHandle + HasDisplayHandle>(
+        handle: &H,
+    ) -> Result<Self, ListenerError> {
+        let listener = KeyboardListener::attatch(handle)?;
+        let hotkeys = Arc::new(Mutex::new(Hotkeys::default()));
+
+        // the receiver is closed when the listener is detached, which stops the thread
+        let receiver = listener.receiver();
+        let thread_hotkeys = Arc::clone(&hotkeys);
+
+        thread::spawn(move || {
+            while let Ok(event) = receiver.recv() {
+                let Event::Press {
+                    key,
+                    repeat_count: 0,
+                } = &event
+                else {
+                    continue;
+                };
+
+                let callbacks = thread_hotkeys
+                    .lock()
+                    .expect("poisoned hotkeys")
+                    .matching(key);
+
+                // called without the lock held so callbacks can (un)register hotkeys
+                for callback in callbacks {
+                    callback(key);
+                }
+            }
+        });
+
+        Ok(Self { listener, hotkeys })
+    }
+
+    /// Parses `accelerator` (see: [`Accelerator`]) and calls `callback` whenever it is pressed.
+    pub fn register<F>(&self, accelerator: &str, callback: F) -> Result<HotkeyId, AcceleratorError>
+    where
+        F: Fn(&KeyEvent) + Send + Sync + 'static,
+    {
+        Ok(self.register_accelerator(accelerator.parse()?, callback))
+    }
+
+    /// See: [`HotkeyListener::register`]
+    pub fn register_accelerator<F>(&self, accelerator: Accelerator, callback: F) -> HotkeyId
+    where
+        F: Fn(&KeyEvent) + Send + Sync + 'static,
+    {
+        let mut hotkeys = self.hotkeys.lock().expect("poisoned hotkeys");
+
+        hotkeys.next_id += 1;
+        let id = HotkeyId(hotkeys.next_id);
+
+        hotkeys.bindings.push((id, accelerator, Arc::new(callback)));
+
+        id
+    }
+
+    /// Returns `false` if `id` wasn't registered, or was already unregistered.
+    pub fn unregister(&self, id: HotkeyId) -> bool {
+        let mut hotkeys = self.hotkeys.lock().expect("poisoned hotkeys");
+        let len = hotkeys.bindings.len();
+
+        hotkeys.bindings.retain(|(i, ..)| *i != id);
+
+        hotkeys.bindings.len() != len
+    }
+
+    /// Every registered accelerator in the order they were registered, e.g. for checking them
+    /// with [`find_conflicts`].
+    pub fn accelerators(&self) -> Vec<Accelerator> {
+        let hotkeys = self.hotkeys.lock().expect("poisoned hotkeys");
+
+        hotkeys.bindings.iter().map(|(_, a, _)| a.clone()).collect()
+    }
+
+    /// See: [`KeyboardListener::detach`]
+    pub fn detach(self) -> Result<(), ListenerError> {
+        self.listener.detach()
+    }
+
+    /// See: [`KeyboardListener::handle`]
+    pub fn handle(&self) -> ListenerHandle {
+        self.listener.handle()
+    }
+}
+
+impl fmt::Debug for HotkeyListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let accelerators: Vec<Accelerator> = self
+            .hotkeys
+            .lock()
+            .map(|h| h.bindings.iter().map(|(_, a, _)| a.clone()).collect())
+            .unwrap_or_default();
+
+        f.debug_struct("HotkeyListener")
+            .field("listener", &self.listener)
+            .field("accelerators", &accelerators)
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct Hotkeys {
+    next_id: u64,
+    bindings: Vec<(HotkeyId, Accelerator, Callback)>,
+}
+
+impl Hotkeys {
+    fn matching(&self, key: &KeyEvent) -> Vec<Callback> {
+        self.bindings
+            .iter()
+            .filter(|(_, accelerator, _)| accelerator.matches(key))
+            .map(|(_, _, callback)| Arc::clone(callback))
+            .collect()
+    }
+}
@@ -0,0 +1,550 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use super::{Accelerator, AcceleratorError};
+use crate::{Event, Key, KeyEvent};
+
+/// Accelerators pressed one after the other, such as `Ctrl+X Ctrl+S` or `g g`.
+///
+/// Parsed from accelerators (see: [`Accelerator`]) separated by whitespace.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeySequence(Vec<Accelerator>);
+
+impl KeySequence {
+    /// Returns `None` if `accelerators` is empty.
+    pub fn new(accelerators: Vec<Accelerator>) -> Option<Self> {
+        (!accelerators.is_empty()).then_some(Self(accelerators))
+    }
+
+    pub fn accelerators(&self) -> &[Accelerator] {
+        &self.0
+    }
+}
+
+impl From<Accelerator> for KeySequence {
+    fn from(accelerator: Accelerator) -> Self {
+        Self(vec![accelerator])
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = AcceleratorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let accelerators = s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+
+        Self::new(accelerators).ok_or(AcceleratorError::Empty)
+    }
+}
+
+impl Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, accelerator) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{accelerator}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// written as the string they are parsed from
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeySequence {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeySequence {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// What a key press did to a [`SequenceMatcher`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum SequenceMatch<'a, T> {
+    /// The key doesn't start a sequence, or isn't a press at all.
+    NoMatch,
+    /// The keys pressed so far start one or more sequences, e.g. for showing `Ctrl+X-` in a
+    /// status line.
+    Partial(&'a [Accelerator]),
+    Matched(&'a T),
+    /// The key doesn't continue the keys pressed so far, which are dropped along with the key.
+    Aborted,
+}
+
+/// Matches [`KeySequence`]s against the events of a listener.
+///
+/// The sequences are kept in a prefix tree, so sequences sharing their first keys are matched
+/// together. A sequence that is the start of another (e.g. `Ctrl+X` and `Ctrl+X Ctrl+S`) matches
+/// as soon as it is pressed, so the longer one can't be reached. This is also the case for
+/// accelerators written differently that match the same key, e.g. `Ctrl+KeyX` and `Ctrl+X`, the
+/// same as [`find_conflicts`](crate::find_conflicts) reports them.
+///
+/// Pressing a modifier on its own never aborts a sequence, so `Ctrl+X Ctrl+S` can be typed while
+/// holding control or by pressing it again.
+#[derive(Debug)]
+pub struct SequenceMatcher<T> {
+    root: Node<T>,
+    timeout: Option<Duration>,
+
+    // the keys pressed so far, and the index of the child they lead to in each node for every
+    // sequence they start. accelerators written differently can match the same key, e.g.
+    // `Ctrl+KeyX` and `Ctrl+X`, so there may be several
+    pending: Vec<Accelerator>,
+    paths: Vec<Vec<usize>>,
+    last_press: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    value: Option<T>,
+    children: Vec<(Accelerator, Node<T>)>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            value: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl<T> SequenceMatcher<T> {
+    /// The default timeout between the keys of a sequence, the same as vim's `timeoutlen`
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        Self {
+            root: Node::new(),
+            timeout: Some(Self::DEFAULT_TIMEOUT),
+            pending: Vec::new(),
+            paths: Vec::new(),
+            last_press: None,
+        }
+    }
+
+    /// Sets how long to wait for the next key of a sequence before dropping the keys pressed
+    /// so far, `None` waits forever like emacs.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the value previously bound to `sequence`, if any.
+    ///
+    /// Any keys pressed so far are dropped, since they may no longer lead anywhere.
+    pub fn insert(&mut self, sequence: KeySequence, value: T) -> Option<T> {
+        self.reset();
+
+        let mut node = &mut self.root;
+
+        for accelerator in sequence.0 {
+            let i = match node.children.iter().position(|(a, _)| *a == accelerator) {
+                Some(i) => i,
+                None => {
+                    node.children.push((accelerator, Node::new()));
+                    node.children.len() - 1
+                },
+            };
+
+            node = &mut node.children[i].1;
+        }
+
+        node.value.replace(value)
+    }
+
+    /// Returns the value bound to `sequence`, if any.
+    ///
+    /// Any keys pressed so far are dropped, since they may no longer lead anywhere.
+    pub fn remove(&mut self, sequence: &KeySequence) -> Option<T> {
+        self.reset();
+
+        remove(&mut self.root, &sequence.0)
+    }
+
+    /// The keys of the partially matched sequence, empty if there isn't one.
+    pub fn pending(&self) -> &[Accelerator] {
+        &self.pending
+    }
+
+    /// Every sequence and its value, sequences that start the same way are next to each other.
+    pub fn iter(&self) -> impl Iterator<Item = (KeySequence, &T)> {
+        let mut sequences = Vec::new();
+        collect(&self.root, &mut Vec::new(), &mut sequences);
+
+        sequences.into_iter()
+    }
+
+    /// Drops the keys pressed so far.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.paths.clear();
+        self.last_press = None;
+    }
+
+    /// Drops the keys pressed so far if the timeout has passed since the last one, returns
+    /// `true` if it did.
+    ///
+    /// Timeouts are otherwise only noticed on the next press, this is for clearing a status line
+    /// on time, e.g. after [`KeyboardListener::recv_timeout`](crate::KeyboardListener::recv_timeout).
+    pub fn check_timeout(&mut self, now: SystemTime) -> bool {
+        if self.timed_out(now) {
+            self.reset();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Repeated presses from holding a key down are ignored, the same as releases.
+    pub fn feed(&mut self, event: &Event) -> SequenceMatch<'_, T> {
+        match event {
+            Event::Press {
+                key,
+                repeat_count: 0,
+            } => self.press(key),
+            _ => SequenceMatch::NoMatch,
+        }
+    }
+
+    // whether `key` would start or continue a sequence, ignoring the timeout
+    pub(crate) fn accepts(&self, key: &KeyEvent) -> bool {
+        !self.pending.is_empty() || self.root.children.iter().any(|(a, _)| a.matches(key))
+    }
+
+    fn press(&mut self, key: &KeyEvent) -> SequenceMatch<'_, T> {
+        self.check_timeout(key.timestamp);
+
+        let starts = if self.paths.is_empty() {
+            vec![Vec::new()]
+        } else {
+            std::mem::take(&mut self.paths)
+        };
+
+        let paths: Vec<Vec<usize>> = starts
+            .iter()
+            .flat_map(|path| {
+                self.node(path)
+                    .children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (a, _))| a.matches(key))
+                    .map(move |(i, _)| [path.as_slice(), &[i]].concat())
+            })
+            .collect();
+
+        if paths.is_empty() {
+            if self.pending.is_empty() {
+                return SequenceMatch::NoMatch;
+            }
+
+            if is_modifier_key(&key.key) {
+                self.paths = starts;
+                return SequenceMatch::Partial(&self.pending);
+            }
+
+            self.reset();
+            return SequenceMatch::Aborted;
+        }
+
+        // the first sequence the key completes, in the order they were inserted
+        if let Some(path) = paths
+            .iter()
+            .find(|path| self.node(path).value.is_some())
+            .cloned()
+        {
+            self.reset();
+
+            return self
+                .node(&path)
+                .value
+                .as_ref()
+                .map_or(SequenceMatch::NoMatch, SequenceMatch::Matched);
+        }
+
+        let (last, parent) = paths[0].split_last().expect("a path has at least one node");
+        let accelerator = self.node(parent).children[*last].0.clone();
+
+        self.pending.push(accelerator);
+        self.paths = paths;
+        self.last_press = Some(key.timestamp);
+
+        SequenceMatch::Partial(&self.pending)
+    }
+
+    fn node(&self, path: &[usize]) -> &Node<T> {
+        path.iter().fold(&self.root, |node, i| &node.children[*i].1)
+    }
+
+    fn timed_out(&self, now: SystemTime) -> bool {
+        match (self.timeout, self.last_press) {
+            (Some(timeout), Some(last_press)) => {
+                now.duration_since(last_press).unwrap_or_default() > timeout
+            },
+            _ => false,
+        }
+    }
+}
+
+impl<T> Default for SequenceMatcher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn collect<'a, T>(
+    node: &'a Node<T>,
+    prefix: &mut Vec<Accelerator>,
+    sequences: &mut Vec<(KeySequence, &'a T)>,
+) {
+    if let Some(value) = &node.value {
+        sequences.push((KeySequence(prefix.clone()), value));
+    }
+
+    for (accelerator, child) in &node.children {
+        prefix.push(accelerator.clone());
+        collect(child, prefix, sequences);
+        prefix.pop();
+    }
+}
+
+fn remove<T>(node: &mut Node<T>, sequence: &[Accelerator]) -> Option<T> {
+    let Some((first, rest)) = sequence.split_first() else {
+        return node.value.take();
+    };
+
+    let i = node.children.iter().position(|(a, _)| a == first)?;
+    let child = &mut node.children[i].1;
+    let value = remove(child, rest);
+
+    // nothing else goes through this child anymore
+    if child.value.is_none() && child.children.is_empty() {
+        node.children.remove(i);
+    }
+
+    value
+}
+
+// pressed before the key of an accelerator, so they shouldn't abort a sequence
+fn is_modifier_key(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Alt
+            | Key::AltGraph
+            | Key::Control
+            | Key::Fn
+            | Key::Hyper
+            | Key::Meta
+            | Key::Shift
+            | Key::Super
+            | Key::Symbol
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Code, Modifiers};
+
+    fn matcher(sequences: &[(&str, u32)]) -> SequenceMatcher<u32> {
+        let mut matcher = SequenceMatcher::new();
+
+        for (sequence, value) in sequences {
+            matcher.insert(sequence.parse().unwrap(), *value);
+        }
+
+        matcher
+    }
+
+    fn accelerators(sequence: &str) -> Vec<Accelerator> {
+        sequence
+            .parse::<KeySequence>()
+            .unwrap()
+            .accelerators()
+            .to_vec()
+    }
+
+    fn key(key: Key, modifiers: Modifiers) -> KeyEvent {
+        KeyEvent::test(key, Code::Unidentified, 0, modifiers)
+    }
+
+    fn ctrl(c: &str) -> Event {
+        Event::Press {
+            key: key(Key::Character(c.to_string()), Modifiers::CONTROL),
+            repeat_count: 0,
+        }
+    }
+
+    #[test]
+    fn full_match() {
+        let mut matcher = matcher(&[("Ctrl+X Ctrl+S", 1)]);
+
+        assert_eq!(
+            matcher.feed(&ctrl("x")),
+            SequenceMatch::Partial(&accelerators("Ctrl+X"))
+        );
+        assert_eq!(matcher.feed(&ctrl("s")), SequenceMatch::Matched(&1));
+        assert!(matcher.pending().is_empty());
+
+        // and again from the start
+        assert_eq!(
+            matcher.feed(&ctrl("x")),
+            SequenceMatch::Partial(&accelerators("Ctrl+X"))
+        );
+    }
+
+    #[test]
+    fn no_match() {
+        let mut matcher = matcher(&[("Ctrl+X Ctrl+S", 1)]);
+
+        assert_eq!(matcher.feed(&ctrl("s")), SequenceMatch::NoMatch);
+        assert_eq!(
+            matcher.feed(&Event::Release(key(
+                Key::Character("x".to_string()),
+                Modifiers::CONTROL
+            ))),
+            SequenceMatch::NoMatch
+        );
+        // holding the key down
+        assert_eq!(
+            matcher.feed(&Event::Press {
+                key: key(Key::Character("x".to_string()), Modifiers::CONTROL),
+                repeat_count: 1,
+            }),
+            SequenceMatch::NoMatch
+        );
+        assert!(matcher.pending().is_empty());
+    }
+
+    #[test]
+    fn aborted() {
+        let mut matcher = matcher(&[("Ctrl+X Ctrl+S", 1)]);
+
+        matcher.feed(&ctrl("x"));
+        assert_eq!(matcher.feed(&ctrl("f")), SequenceMatch::Aborted);
+        assert!(matcher.pending().is_empty());
+
+        // the key that aborted is dropped too, rather than starting another sequence
+        assert_eq!(matcher.feed(&ctrl("s")), SequenceMatch::NoMatch);
+    }
+
+    #[test]
+    fn modifiers_pass_through() {
+        let mut matcher = matcher(&[("Ctrl+X Ctrl+S", 1)]);
+
+        matcher.feed(&ctrl("x"));
+
+        // pressing control again before the second key
+        let control = Event::Press {
+            key: key(Key::Control, Modifiers::CONTROL),
+            repeat_count: 0,
+        };
+        assert_eq!(
+            matcher.feed(&control),
+            SequenceMatch::Partial(&accelerators("Ctrl+X"))
+        );
+        assert_eq!(matcher.feed(&ctrl("s")), SequenceMatch::Matched(&1));
+    }
+
+    #[test]
+    fn prefix_shadows_longer_sequence() {
+        let mut matcher = matcher(&[("Ctrl+X", 1), ("Ctrl+X Ctrl+S", 2)]);
+
+        assert_eq!(matcher.feed(&ctrl("x")), SequenceMatch::Matched(&1));
+        assert_eq!(matcher.feed(&ctrl("s")), SequenceMatch::NoMatch);
+    }
+
+    #[test]
+    fn timeout() {
+        let mut matcher = matcher(&[("Ctrl+X Ctrl+S", 1)]);
+        matcher.set_timeout(Some(Duration::from_secs(1)));
+
+        let x = ctrl("x");
+        let Event::Press { key: x_key, .. } = &x else {
+            unreachable!()
+        };
+        let start = x_key.timestamp;
+
+        matcher.feed(&x);
+        assert!(!matcher.check_timeout(start + Duration::from_millis(500)));
+        assert_eq!(matcher.pending(), accelerators("Ctrl+X"));
+
+        assert!(matcher.check_timeout(start + Duration::from_secs(2)));
+        assert!(matcher.pending().is_empty());
+        assert!(!matcher.check_timeout(start + Duration::from_secs(3)));
+
+        // noticed on the next press too
+        matcher.feed(&x);
+        let mut s = key(Key::Character("s".to_string()), Modifiers::CONTROL);
+        s.timestamp = start + Duration::from_secs(2);
+        assert_eq!(
+            matcher.feed(&Event::Press {
+                key: s,
+                repeat_count: 0,
+            }),
+            SequenceMatch::NoMatch
+        );
+
+        // unless there is no timeout
+        matcher.set_timeout(None);
+        matcher.feed(&x);
+        assert!(!matcher.check_timeout(start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn remove_prunes_tree() {
+        let mut matcher = matcher(&[("Ctrl+X Ctrl+S", 1), ("Ctrl+X Ctrl+F", 2)]);
+
+        assert_eq!(matcher.remove(&"Ctrl+X Ctrl+C".parse().unwrap()), None);
+        assert_eq!(matcher.remove(&"Ctrl+X".parse().unwrap()), None);
+
+        assert_eq!(matcher.remove(&"Ctrl+X Ctrl+S".parse().unwrap()), Some(1));
+        assert_eq!(matcher.root.children.len(), 1);
+        assert_eq!(matcher.root.children[0].1.children.len(), 1);
+
+        assert_eq!(matcher.remove(&"Ctrl+X Ctrl+F".parse().unwrap()), Some(2));
+        assert!(matcher.root.children.is_empty());
+        assert_eq!(matcher.iter().count(), 0);
+        assert_eq!(matcher.feed(&ctrl("x")), SequenceMatch::NoMatch);
+    }
+
+    #[test]
+    fn overlapping_accelerators() {
+        let mut overlapping = matcher(&[("Ctrl+KeyX Ctrl+S", 1), ("Ctrl+X Ctrl+F", 2)]);
+        let x = Event::Press {
+            key: KeyEvent::test(
+                Key::Character("x".to_string()),
+                Code::KeyX,
+                0,
+                Modifiers::CONTROL,
+            ),
+            repeat_count: 0,
+        };
+
+        // either sequence can be finished, whichever was inserted first
+        assert_eq!(
+            overlapping.feed(&x),
+            SequenceMatch::Partial(&accelerators("Ctrl+KeyX"))
+        );
+        assert_eq!(overlapping.feed(&ctrl("f")), SequenceMatch::Matched(&2));
+
+        overlapping.feed(&x);
+        assert_eq!(overlapping.feed(&ctrl("s")), SequenceMatch::Matched(&1));
+
+        // a complete sequence shadows the ones it overlaps with
+        let mut shadowed = matcher(&[("Ctrl+X Ctrl+S", 1), ("Ctrl+KeyX", 2)]);
+        assert_eq!(shadowed.feed(&x), SequenceMatch::Matched(&2));
+    }
+}
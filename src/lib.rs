@@ -1,38 +1,192 @@
 #![allow(clippy::type_complexity)]
 
+#[cfg(feature = "hotkeys")]
+mod hotkeys;
 mod platform_impl;
+mod repeat;
+mod state;
 
 use std::collections::HashMap;
 use std::fmt::{self, Display};
-use std::sync::RwLock;
-use std::time::SystemTime;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, SystemTime};
 
+#[cfg(feature = "async")]
+use futures_util::Stream;
 use kanal::{Receiver, Sender};
 pub use raw_window_handle::HandleError;
-use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+pub use smol_str::SmolStr;
 
+#[cfg(all(feature = "hotkeys", target_os = "linux"))]
+pub use crate::hotkeys::find_unproducible;
+#[cfg(all(feature = "hotkeys", feature = "serde"))]
+pub use crate::hotkeys::Bindings;
+#[cfg(feature = "config")]
+pub use crate::hotkeys::ConfigError;
+#[cfg(feature = "hotkeys")]
+pub use crate::hotkeys::{
+    find_conflicts, Accelerator, AcceleratorError, AcceleratorFormat, AcceleratorStyle, Conflict,
+    HotkeyId, HotkeyListener, KeyNames, KeySequence, Keymap, KeymapStack, ModeAction, ModeSwitch,
+    SequenceMatch, SequenceMatcher, Trigger,
+};
 pub use crate::platform_impl::AttachError;
+#[cfg(target_os = "linux")]
+pub use crate::platform_impl::SendError;
+pub use crate::state::KeyboardState;
 
 /// Re-exported from [`keyboard-types`](https://crates.io/crates/keyboard-types)
 pub type Key = keyboard_types::Key;
 /// Re-exported from [`keyboard-types`](https://crates.io/crates/keyboard-types)
+pub type Code = keyboard_types::Code;
+/// Re-exported from [`keyboard-types`](https://crates.io/crates/keyboard-types)
+pub type Location = keyboard_types::Location;
+/// Re-exported from [`keyboard-types`](https://crates.io/crates/keyboard-types)
 pub type Modifiers = keyboard_types::Modifiers;
 
 #[derive(Clone, Debug, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyEvent {
     pub key: Key,
+    /// The text typed by the key press with its modifiers applied, in the layout active when it
+    /// was pressed. `None` for releases and keys that don't type anything, such as control
+    /// characters and dead keys
+    pub text: Option<SmolStr>,
+    /// The text of a dead key or compose sequence finished by this key press, e.g. `é` when
+    /// pressing `e` after the dead key `´` (see: [`Key::Dead`], [`Key::Compose`]), which is also
+    /// its `text`
+    pub composed: Option<SmolStr>,
+    /// The physical key, which doesn't depend on the keyboard layout
+    pub code: Code,
+    /// Where the key is on the keyboard, for telling apart e.g. left and right shift
+    pub location: Location,
     pub modifiers: Modifiers,
     pub timestamp: SystemTime,
+    /// Whether the event was made up by the listener rather than sent by the OS, see:
+    /// [`KeyboardListener::release_keys_on_focus_loss`]
+    pub synthetic: bool,
 
     raw: platform_impl::RawKeyEventData,
 }
 
+// every backend reports the physical key, which is enough to know where it is
+pub(crate) fn code_location(code: Code) -> Location {
+    match code {
+        Code::AltLeft | Code::ControlLeft | Code::MetaLeft | Code::ShiftLeft => Location::Left,
+        Code::AltRight | Code::ControlRight | Code::MetaRight | Code::ShiftRight => Location::Right,
+        Code::Numpad0
+        | Code::Numpad1
+        | Code::Numpad2
+        | Code::Numpad3
+        | Code::Numpad4
+        | Code::Numpad5
+        | Code::Numpad6
+        | Code::Numpad7
+        | Code::Numpad8
+        | Code::Numpad9
+        | Code::NumpadAdd
+        | Code::NumpadBackspace
+        | Code::NumpadClear
+        | Code::NumpadClearEntry
+        | Code::NumpadComma
+        | Code::NumpadDecimal
+        | Code::NumpadDivide
+        | Code::NumpadEnter
+        | Code::NumpadEqual
+        | Code::NumpadHash
+        | Code::NumpadMemoryAdd
+        | Code::NumpadMemoryClear
+        | Code::NumpadMemoryRecall
+        | Code::NumpadMemoryStore
+        | Code::NumpadMemorySubtract
+        | Code::NumpadMultiply
+        | Code::NumpadParenLeft
+        | Code::NumpadParenRight
+        | Code::NumpadStar
+        | Code::NumpadSubtract => Location::Numpad,
+        _ => Location::Standard,
+    }
+}
+
+#[cfg(test)]
+impl KeyEvent {
+    // an event for `key` on the key with the platform scan code `scancode`, like the backends
+    // make but without the keysym or virtual key
+    pub(crate) fn test(key: Key, code: Code, scancode: u32, modifiers: Modifiers) -> Self {
+        Self {
+            key,
+            text: None,
+            composed: None,
+            code,
+            location: code_location(code),
+            modifiers,
+            timestamp: SystemTime::now(),
+            synthetic: false,
+            raw: platform_impl::RawKeyEventData::test(scancode),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Event {
-    Press { key: KeyEvent, repeat_count: usize },
+    /// `repeat_count` is the number of times the key was pressed before this without being
+    /// released, which is 0 for the first press
+    Press {
+        key: KeyEvent,
+        repeat_count: usize,
+    },
     Release(KeyEvent),
+    /// The active modifiers changed, lock keys included.
+    ///
+    /// This is also sent for changes without a key event, e.g. a modifier released while the
+    /// window didn't have focus.
+    ModifiersChanged(Modifiers),
+    /// Caps, num or scroll lock was turned on or off, `lock` is one of `CAPS_LOCK`, `NUM_LOCK`
+    /// and `SCROLL_LOCK`.
+    ///
+    /// On Linux, scroll lock is only reported if the keymap gives it a modifier, which the
+    /// default keymaps don't.
+    ///
+    /// Sent after the [`Event::ModifiersChanged`] it is part of, e.g. for showing a caps lock
+    /// warning on a password field.
+    LockChanged {
+        lock: Modifiers,
+        locked: bool,
+    },
+    /// The window got keyboard focus.
+    FocusGained,
+    /// The window lost keyboard focus, any keys still held down are released in another window.
+    FocusLost,
+    /// The active keyboard layout changed, e.g. the user switched to another installed one.
+    LayoutChanged(LayoutInfo),
+    /// An input method started composing text, e.g. a Japanese word typed as its reading. The
+    /// keys typing it may not be sent while composing.
+    ///
+    /// **Note: Only sent on Wayland, by compositors supporting `text-input-unstable-v3`, when
+    /// enabled with [`KeyboardListenerBuilder::text_input`]. X11 input methods (XIM) are not
+    /// implemented, so text composed with them on X11 is not sent. The position of the text
+    /// cursor isn't known to the listener, so it isn't given to the compositor either, which
+    /// places the input method's candidate window wherever it chooses.**
+    CompositionStart,
+    /// The text being composed changed, for showing it inline where it will be inserted.
+    /// `cursor` is the byte range of `text` the cursor is on, which is empty if nothing is
+    /// selected, or `None` if the cursor should be hidden.
+    CompositionUpdate {
+        text: String,
+        cursor: Option<Range<usize>>,
+    },
+    /// Composition finished, `Some` with the text to insert or `None` if it was cancelled.
+    CompositionEnd(Option<String>),
+}
+
+impl Event {
+    /// Whether this is a press generated by holding the key down
+    pub fn is_repeat(&self) -> bool {
+        matches!(self, Event::Press { repeat_count, .. } if *repeat_count > 0)
+    }
 }
 
 #[derive(PartialEq, Hash, Eq)]
@@ -42,7 +196,83 @@ unsafe impl Send for SendSyncRwh {}
 unsafe impl Sync for SendSyncRwh {}
 
 lazy_static::lazy_static! {
-    pub(crate) static ref CHANNELS: RwLock<HashMap<SendSyncRwh, (Sender<Event>, Receiver<Event>)>> = RwLock::new(HashMap::new());
+    // key: window, value: the channel of every listener attached to it
+    static ref CHANNELS: RwLock<HashMap<SendSyncRwh, Vec<Arc<Channel>>>> = RwLock::new(HashMap::new());
+    // the platform side of every attached window, shared by the listeners attached to it
+    static ref WINDOWS: Mutex<HashMap<SendSyncRwh, Weak<AttachedWindow>>> = Mutex::new(HashMap::new());
+}
+
+// called from the platform event handlers once an event has been translated
+pub(crate) fn send_event(window: platform_impl::PlatformWindowHandle, event: Event) {
+    let channels = CHANNELS.read().expect("poisoned channels");
+
+    // the listeners may have been detached while the event was being translated
+    for channel in channels.get(&SendSyncRwh(window)).into_iter().flatten() {
+        channel.send(event.clone());
+    }
+}
+
+// the events of an attached listener and the keyboard state they add up to
+#[derive(Debug)]
+struct Channel {
+    // `None` once the listener is detached, which closes the receiver after the events queued
+    sender: Mutex<Option<Sender<Event>>>,
+    receiver: Receiver<Event>,
+    state: Mutex<KeyboardState>,
+    release_on_focus_loss: AtomicBool,
+}
+
+impl Channel {
+    fn new() -> Self {
+        let (sender, receiver) = kanal::unbounded();
+
+        Self {
+            sender: Mutex::new(Some(sender)),
+            receiver,
+            state: Mutex::new(KeyboardState::default()),
+            release_on_focus_loss: AtomicBool::new(false),
+        }
+    }
+
+    // the state is updated first so it is never behind the events that have been received
+    fn send(&self, event: Event) {
+        let release =
+            matches!(event, Event::FocusLost) && self.release_on_focus_loss.load(Ordering::Relaxed);
+
+        let events = match self.state.lock() {
+            Ok(mut state) if release => {
+                let mut events = state.apply(event);
+
+                for release in state.synthetic_releases() {
+                    events.extend(state.apply(release));
+                }
+
+                events
+            },
+            Ok(mut state) => state.apply(event),
+            Err(_) => vec![event],
+        };
+
+        let Ok(sender) = self.sender.lock() else {
+            return;
+        };
+
+        // detached while the event was being applied
+        let Some(sender) = sender.as_ref() else {
+            return;
+        };
+
+        for event in events {
+            let _ = sender.send(event);
+        }
+    }
+
+    // wakes up every thread blocked receiving, once they have received the events queued
+    fn close(&self) {
+        if let Ok(mut sender) = self.sender.lock() {
+            sender.take();
+        }
+    }
 }
 
 #[non_exhaustive]
@@ -51,6 +281,7 @@ pub enum ListenerError {
     InvalidHandle,
     HandleError(HandleError),
     AttachError(platform_impl::AttachError),
+    DetachError(platform_impl::AttachError),
 }
 
 impl Display for ListenerError {
@@ -59,81 +290,816 @@ impl Display for ListenerError {
             ListenerError::InvalidHandle => write!(f, "invalid handle provided for this platform"),
             ListenerError::HandleError(h) => write!(f, "{h}"),
             ListenerError::AttachError(e) => write!(f, "{e}"),
+            ListenerError::DetachError(e) => write!(f, "{e}"),
         }
     }
 }
 
+#[derive(Debug)]
 pub enum ReceiveError {
     PoisonError,
-    Kanal(kanal::ReceiveError),
+    /// The listener was detached, or dropped, while receiving.
+    ListenerClosed,
+    /// The listener stopped because of an error, e.g. `/dev/input` couldn't be read anymore.
+    ListenerFailed(platform_impl::AttachError),
 }
 
 impl Display for ReceiveError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ReceiveError::PoisonError => {
-                write!(f, "RwLock poisoned in try_recv")
+                write!(f, "RwLock poisoned while receiving")
             },
-            ReceiveError::Kanal(k) => write!(f, "{k}"),
+            ReceiveError::ListenerClosed => write!(f, "listener closed"),
+            ReceiveError::ListenerFailed(e) => write!(f, "listener failed: {e}"),
         }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct KeyboardListener {
-    inner: platform_impl::KeyboardListener,
+    inner: Arc<AttachedListener>,
 }
 
-impl KeyboardListener {
-    pub fn attatch<H: HasWindowHandle>(handle: &H) -> Result<Self, ListenerError> {
+/// Attaches a [`KeyboardListener`] with options, [`KeyboardListener::attatch`] uses the defaults.
+///
+/// The first listener attached to a window decides the options for every listener attached to it
+/// later, as long as it stays attached.
+#[derive(Clone, Debug, Default)]
+pub struct KeyboardListenerBuilder {
+    text_input: bool,
+}
+
+impl KeyboardListenerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds the compositor's input method (`text-input-unstable-v3`) on Wayland, which sends the
+    /// composition events (see: [`Event::CompositionStart`]). Off by default.
+    ///
+    /// This takes over text input for the window's surface: the input method is enabled for as long
+    /// as the surface has focus, and disabled when it loses it or the listener is detached. Don't
+    /// enable it if the toolkit handles input methods itself, e.g. winit's IME support, as both
+    /// would be changing the same state.
+    pub fn text_input(mut self, enable: bool) -> Self {
+        self.text_input = enable;
+        self
+    }
+
+    /// See: [`KeyboardListener::attatch`]
+    pub fn attatch<H: HasWindowHandle + HasDisplayHandle>(
+        &self,
+        handle: &H,
+    ) -> Result<KeyboardListener, ListenerError> {
         let rwh = handle
             .window_handle()
             .map_err(ListenerError::HandleError)?
             .as_raw();
+        let rdh = handle
+            .display_handle()
+            .map_err(ListenerError::HandleError)?
+            .as_raw();
+
+        let handle = platform_impl::window_handle(rwh, rdh)?;
 
-        let slf = Self {
-            inner: platform_impl::KeyboardListener::from_raw_window_handle(rwh)?,
+        // held until the window is attached, so a listener attaching at the same time waits
+        let mut windows = WINDOWS
+            .lock()
+            .map_err(|_| ListenerError::AttachError(AttachError::PoisonError))?;
+
+        let listener = KeyboardListener {
+            inner: Arc::new(AttachedListener {
+                handle,
+                window: Mutex::new(None),
+                channel: Arc::new(Channel::new()),
+            }),
         };
 
+        // added before attaching so no event is missed
         CHANNELS
             .write()
             .map_err(|_| ListenerError::AttachError(AttachError::PoisonError))?
-            .insert(
-                SendSyncRwh(slf.inner.platform_window_handle()),
-                kanal::unbounded(),
-            );
+            .entry(SendSyncRwh(handle))
+            .or_default()
+            .push(Arc::clone(&listener.inner.channel));
+
+        let window = match windows.get(&SendSyncRwh(handle)).and_then(Weak::upgrade) {
+            Some(window) => window,
+            None => {
+                let window = Arc::new(AttachedWindow(
+                    platform_impl::KeyboardListener::from_raw_window_handle(
+                        rwh,
+                        rdh,
+                        self.text_input,
+                    )?,
+                ));
+
+                // if this fails, dropping `listener` cleans up what was attached so far
+                window.0.attatch().map_err(ListenerError::AttachError)?;
+                windows.insert(SendSyncRwh(handle), Arc::downgrade(&window));
 
-        slf.inner.attatch().map_err(ListenerError::AttachError)?;
+                window
+            },
+        };
 
-        Ok(slf)
+        *listener
+            .inner
+            .window
+            .lock()
+            .map_err(|_| ListenerError::AttachError(AttachError::PoisonError))? = Some(window);
+
+        Ok(listener)
+    }
+}
+
+/// A handle that can detach a [`KeyboardListener`] from another thread.
+///
+/// Holding a handle doesn't keep the listener attached, it is still detached once every clone of
+/// the [`KeyboardListener`] has been dropped.
+#[derive(Clone, Debug)]
+pub struct ListenerHandle {
+    inner: Weak<AttachedListener>,
+}
+
+impl ListenerHandle {
+    /// See: [`KeyboardListener::detach`]
+    pub fn detach(&self) -> Result<(), ListenerError> {
+        match self.inner.upgrade() {
+            Some(listener) => listener.detach(),
+            None => Ok(()),
+        }
+    }
+}
+
+// shared by every clone of a listener, it is detached once the last one is dropped
+#[derive(Debug)]
+struct AttachedListener {
+    handle: platform_impl::PlatformWindowHandle,
+    // `None` once detached
+    window: Mutex<Option<Arc<AttachedWindow>>>,
+    channel: Arc<Channel>,
+}
+
+// attached along with the first listener on the window, and detached along with the last
+#[derive(Debug)]
+struct AttachedWindow(platform_impl::KeyboardListener);
+
+impl AttachedListener {
+    fn window(&self) -> Option<Arc<AttachedWindow>> {
+        self.window.lock().ok()?.clone()
+    }
+
+    fn is_attached(&self) -> Result<bool, ReceiveError> {
+        let window = self.window.lock().map_err(|_| ReceiveError::PoisonError)?;
+
+        Ok(window.is_some())
+    }
+
+    fn detach(&self) -> Result<(), ListenerError> {
+        let window = self
+            .window
+            .lock()
+            .map_err(|_| ListenerError::DetachError(AttachError::PoisonError))?
+            .take();
+
+        // the other listeners on the window keep receiving
+        {
+            let mut channels = CHANNELS
+                .write()
+                .map_err(|_| ListenerError::DetachError(AttachError::PoisonError))?;
+
+            if let Some(window_channels) = channels.get_mut(&SendSyncRwh(self.handle)) {
+                window_channels.retain(|channel| !Arc::ptr_eq(channel, &self.channel));
+
+                if window_channels.is_empty() {
+                    channels.remove(&SendSyncRwh(self.handle));
+                }
+            }
+        }
+
+        self.channel.close();
+
+        let Some(window) = window else {
+            return Ok(());
+        };
+
+        // the lock stops another listener from attaching to the window while it is detached
+        let mut windows = WINDOWS
+            .lock()
+            .map_err(|_| ListenerError::DetachError(AttachError::PoisonError))?;
+
+        if let Ok(window) = Arc::try_unwrap(window) {
+            windows.remove(&SendSyncRwh(self.handle));
+            window.0.detach().map_err(ListenerError::DetachError)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AttachedListener {
+    fn drop(&mut self) {
+        let _ = self.detach();
+    }
+}
+
+impl KeyboardListener {
+    /// Several listeners can be attached to the same window, each of them receives every event.
+    ///
+    /// See: [`KeyboardListenerBuilder`] for attaching with options.
+    pub fn attatch<H: HasWindowHandle + HasDisplayHandle>(
+        handle: &H,
+    ) -> Result<Self, ListenerError> {
+        KeyboardListenerBuilder::new().attatch(handle)
+    }
+
+    /// Stops listening, every thread receiving gets [`ReceiveError::ListenerClosed`] once it has
+    /// received the events from before.
+    ///
+    /// This also detaches every clone of the listener. Dropping the last clone detaches it
+    /// as well, but any error is ignored.
+    pub fn detach(self) -> Result<(), ListenerError> {
+        self.inner.detach()
+    }
+
+    /// Returns a handle that can detach the listener from another thread.
+    pub fn handle(&self) -> ListenerHandle {
+        ListenerHandle {
+            inner: Arc::downgrade(&self.inner),
+        }
     }
 
     /// See: [`KeyboardListener::try_recv`]
     ///
+    /// Returns once the listener is detached.
+    ///
     /// **Note: This function is blocking!**
     pub fn recv<F>(&self, callback: F)
     where
         F: Fn(Event),
     {
         match self.try_recv(callback) {
-            Ok(..) => (),
+            Ok(..) | Err(ReceiveError::ListenerClosed) => (),
             Err(e) => panic!("failed to receive: {e}"),
         }
     }
 
+    /// Calls `callback` for every event until the listener is detached, which returns
+    /// [`ReceiveError::ListenerClosed`].
+    ///
     /// **Note: This function is blocking!**
     pub fn try_recv<F>(&self, callback: F) -> Result<(), ReceiveError>
     where
         F: Fn(Event),
     {
-        let channels = CHANNELS.read().map_err(|_| ReceiveError::PoisonError)?;
-        let channel = channels
-            .get(&SendSyncRwh(self.inner.platform_window_handle()))
-            .unwrap();
+        let receiver = self.receiver();
+
+        loop {
+            let event = receiver.recv().map_err(|_| ReceiveError::ListenerClosed)?;
+            callback(event);
+        }
+    }
+
+    /// Returns every event received since the last call without blocking.
+    ///
+    /// Meant to be called once per frame from a game loop.
+    pub fn poll(&self) -> Result<Vec<Event>, ReceiveError> {
+        poll_events(&self.receiver())
+    }
+
+    /// Waits up to `timeout` for the next event, `None` if there wasn't one in time.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Event>, ReceiveError> {
+        recv_event_timeout(&self.receiver(), timeout)
+    }
+
+    /// The keys held down and the active modifiers as of the last event the listener saw,
+    /// whether or not the events have been received yet.
+    ///
+    /// Keys that were already down when the listener was attached aren't known, see
+    /// [`KeyboardListener::query_state`] for asking the OS instead.
+    pub fn state(&self) -> Result<KeyboardState, ReceiveError> {
+        if !self.inner.is_attached()? {
+            return Err(ReceiveError::ListenerClosed);
+        }
+
+        let state = self
+            .inner
+            .channel
+            .state
+            .lock()
+            .map_err(|_| ReceiveError::PoisonError)?
+            .clone();
+
+        Ok(state)
+    }
+
+    /// Sends a synthetic [`Event::Release`] (see: [`KeyEvent::synthetic`]) for every key still held
+    /// down when the window loses focus, after [`Event::FocusLost`]. Off by default.
+    ///
+    /// Their real releases go to the window focused instead, so without this the keys would seem
+    /// to be held until they are pressed again.
+    pub fn release_keys_on_focus_loss(&self, release: bool) -> Result<(), ReceiveError> {
+        if !self.inner.is_attached()? {
+            return Err(ReceiveError::ListenerClosed);
+        }
+
+        self.inner
+            .channel
+            .release_on_focus_loss
+            .store(release, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Asks the OS which keys are held down right now.
+    ///
+    /// On X11 (`XQueryKeymap`) that is regardless of which window they were pressed in, `None` if
+    /// the server couldn't be asked. On Windows (`GetKeyboardState`) it is as of the last keyboard
+    /// message the calling thread handled, so it should be called from the window's thread.
+    /// Wayland doesn't let clients ask at all, which is always `None`.
+    pub fn query_state(&self) -> Option<KeyboardState> {
+        self.inner.window()?.0.query_state()
+    }
+
+    /// The keyboard layouts the user can switch between, in the order they are switched.
+    ///
+    /// On X11 and Wayland these are the layouts (groups) of the window's keymap, which are empty
+    /// on Wayland until the compositor has sent it.
+    pub fn layouts(&self) -> Vec<LayoutInfo> {
+        self.inner
+            .window()
+            .map(|window| window.0.layouts())
+            .unwrap_or_default()
+    }
+
+    /// The keyboard layout keys are currently typed with, see: [`Event::LayoutChanged`]
+    pub fn active_layout(&self) -> Option<LayoutInfo> {
+        self.inner.window()?.0.active_layout()
+    }
+
+    /// Returns an iterator that blocks on each event until the listener is detached.
+    pub fn events(&self) -> impl Iterator<Item = Event> {
+        let receiver = self.receiver();
+
+        std::iter::from_fn(move || receiver.recv().ok())
+    }
+
+    /// Returns a stream of every event received from now on, for use in async code instead of
+    /// [`KeyboardListener::recv`].
+    ///
+    /// The stream ends once the listener is detached and the events from before are received.
+    #[cfg(feature = "async")]
+    pub fn stream(&self) -> impl Stream<Item = Event> + Send + Unpin {
+        event_stream(self.receiver().to_async())
+    }
+
+    // closed once the listener is detached
+    fn receiver(&self) -> Receiver<Event> {
+        self.inner.channel.receiver.clone()
+    }
+}
+
+fn poll_events(receiver: &Receiver<Event>) -> Result<Vec<Event>, ReceiveError> {
+    let mut events = Vec::new();
+
+    loop {
+        match receiver.try_recv() {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => return Ok(events),
+            // the events received before the listener closed aren't lost, the next poll fails
+            Err(_) if !events.is_empty() => return Ok(events),
+            Err(_) => return Err(ReceiveError::ListenerClosed),
+        }
+    }
+}
+
+fn recv_event_timeout(
+    receiver: &Receiver<Event>,
+    timeout: Duration,
+) -> Result<Option<Event>, ReceiveError> {
+    match receiver.recv_timeout(timeout) {
+        Ok(event) => Ok(Some(event)),
+        Err(kanal::ReceiveErrorTimeout::Timeout) => Ok(None),
+        Err(_) => Err(ReceiveError::ListenerClosed),
+    }
+}
+
+// kanal's own stream borrows the receiver, so it can't be returned
+#[cfg(feature = "async")]
+fn event_stream(receiver: kanal::AsyncReceiver<Event>) -> impl Stream<Item = Event> + Send + Unpin {
+    Box::pin(futures_util::stream::unfold(
+        receiver,
+        |receiver| async move {
+            let event = receiver.recv().await.ok()?;
+            Some((event, receiver))
+        },
+    ))
+}
+
+/// Listens to every keyboard connected to the system, regardless of which window (if any) has focus.
+///
+/// On Linux the keyboards are read from `/dev/input/event*`, so the user must be allowed to read
+/// those devices (usually by being in the `input` group). Keyboards plugged in after the listener
+/// was created are picked up automatically.
+#[cfg(all(feature = "global", target_os = "linux"))]
+#[derive(Clone, Debug)]
+pub struct GlobalKeyboardListener {
+    inner: platform_impl::GlobalKeyboardListener,
+}
+
+#[cfg(all(feature = "global", target_os = "linux"))]
+impl GlobalKeyboardListener {
+    pub fn new() -> Result<Self, ListenerError> {
+        Ok(Self {
+            inner: platform_impl::GlobalKeyboardListener::new()
+                .map_err(ListenerError::AttachError)?,
+        })
+    }
+
+    /// See: [`GlobalKeyboardListener::try_recv`]
+    ///
+    /// **Note: This function is blocking!**
+    pub fn recv<F>(&self, callback: F)
+    where
+        F: Fn(Event),
+    {
+        match self.try_recv(callback) {
+            Ok(..) | Err(ReceiveError::ListenerClosed) => (),
+            Err(e) => panic!("failed to receive: {e}"),
+        }
+    }
 
+    /// Calls `callback` for every event until the listener stops because of an error, which
+    /// returns [`ReceiveError::ListenerFailed`].
+    ///
+    /// **Note: This function is blocking!**
+    pub fn try_recv<F>(&self, callback: F) -> Result<(), ReceiveError>
+    where
+        F: Fn(Event),
+    {
         loop {
-            let event = channel.1.recv().map_err(ReceiveError::Kanal)?;
+            let event = self
+                .inner
+                .receiver()
+                .recv()
+                .map_err(|_| self.closed_error(ReceiveError::ListenerClosed))?;
             callback(event);
         }
     }
+
+    /// See: [`KeyboardListener::poll`]
+    pub fn poll(&self) -> Result<Vec<Event>, ReceiveError> {
+        poll_events(self.inner.receiver()).map_err(|e| self.closed_error(e))
+    }
+
+    /// See: [`KeyboardListener::recv_timeout`]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Event>, ReceiveError> {
+        recv_event_timeout(self.inner.receiver(), timeout).map_err(|e| self.closed_error(e))
+    }
+
+    /// See: [`KeyboardListener::state`]
+    pub fn state(&self) -> Result<KeyboardState, ReceiveError> {
+        self.inner.state().map_err(|_| ReceiveError::PoisonError)
+    }
+
+    /// Asks every keyboard which of its keys are held down right now (`EVIOCGKEY`), including
+    /// keys that were already down when the listener was created.
+    pub fn query_state(&self) -> Result<KeyboardState, ReceiveError> {
+        self.inner
+            .query_state()
+            .map_err(|_| ReceiveError::PoisonError)
+    }
+
+    /// Returns an iterator that blocks on each event until the listener is dropped, or stops
+    /// because of an error.
+    pub fn events(&self) -> impl Iterator<Item = Event> {
+        let receiver = self.inner.receiver().clone();
+
+        std::iter::from_fn(move || receiver.recv().ok())
+    }
+
+    /// Returns a stream of every event received from now on, for use in async code instead of
+    /// [`GlobalKeyboardListener::recv`].
+    ///
+    /// The stream ends once the listener is dropped, or stops because of an error.
+    #[cfg(feature = "async")]
+    pub fn stream(&self) -> impl Stream<Item = Event> + Send + Unpin {
+        event_stream(self.inner.receiver().clone_async())
+    }
+
+    // the channel is closed once the event thread stops, which may have been because of an error
+    fn closed_error(&self, e: ReceiveError) -> ReceiveError {
+        match (e, self.inner.error()) {
+            (ReceiveError::ListenerClosed, Some(error)) => ReceiveError::ListenerFailed(error),
+            (e, _) => e,
+        }
+    }
+}
+
+/// A keyboard layout installed on the system, see: [`KeyboardListener::layouts`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayoutInfo {
+    /// The name shown to the user, e.g. `German (no dead keys)`
+    pub name: String,
+    /// The BCP 47 tag of the layout's language, e.g. `de-DE`, `None` if it isn't known
+    pub language: Option<String>,
+    pub id: LayoutId,
+}
+
+/// How the OS identifies a keyboard layout.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayoutId {
+    /// The rules, model, layout, variant and options (RMLVO) of the XKB keymap, with only the
+    /// layout and variant of this layout if the keymap has several.
+    ///
+    /// Every field is empty on Wayland, where the compositor only sends the compiled keymap.
+    Xkb {
+        rules: String,
+        model: String,
+        layout: String,
+        variant: String,
+        options: String,
+    },
+    /// The input locale identifier (`HKL`) on Windows
+    Hkl(isize),
+}
+
+/// A keyboard layout, for finding out which keys it has.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Debug)]
+pub struct Layout {
+    inner: platform_impl::Layout,
+}
+
+#[cfg(target_os = "linux")]
+impl Layout {
+    /// Compiles an XKB layout from its name and variant, e.g. `("de", "nodeadkeys")`, an empty
+    /// variant is the default one.
+    ///
+    /// `None` if libxkbcommon couldn't be loaded or there is no such layout.
+    pub fn from_xkb_names(layout: &str, variant: &str) -> Option<Self> {
+        Some(Self {
+            inner: platform_impl::Layout::from_names(layout, variant)?,
+        })
+    }
+
+    /// Whether a key of the layout is `key` when pressed without modifiers, which is how
+    /// [`KeyEvent::key`] reports it.
+    pub fn has_key(&self, key: &Key) -> bool {
+        self.inner.has_key(key)
+    }
+
+    /// Whether the layout has a key at `code`.
+    pub fn has_code(&self, code: Code) -> bool {
+        self.inner.has_code(code)
+    }
+
+    /// Finds the key that types `c` and the modifiers that have to be held for it, e.g. `@` is
+    /// `(Code::KeyQ, Modifiers::ALT_GRAPH)` on a German layout.
+    ///
+    /// Keys without modifiers are preferred, then Shift, AltGr and Shift+AltGr. `None` if the
+    /// layout can't type `c` with a single key, such as a character only typed with a dead key.
+    pub fn find_key_for(&self, c: char) -> Option<(Code, Modifiers)> {
+        self.inner.find_key_for(c)
+    }
+}
+
+/// Sends synthetic key presses, which are received by the focused window the same as real ones.
+///
+/// On X11 this uses the XTEST extension of the display named by `DISPLAY`. Everywhere else (including wayland) a uinput virtual
+/// keyboard is created, which needs write access to `/dev/uinput`. The compositor may take a
+/// moment to notice the new device, so keys sent straight after [`KeySender::new`] can be lost.
+///
+/// Keys are looked up in the active layout, so a [`KeyboardListener`] receives the key that types
+/// what was sent, e.g. sending `"A"` is received as `"a"` with shift held and `"A"` as its text
+/// (see: [`KeyEvent::key`], [`KeyEvent::text`]). The active layout is the X server's, which on
+/// wayland is XWayland's copy of the compositor's. Without either it can't be found out, so the
+/// one set by the `XKB_DEFAULT_*` environment variables is used (US if they aren't set), see
+/// [`KeySender::with_layout`] for giving it instead.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct KeySender {
+    inner: platform_impl::KeySender,
+}
+
+#[cfg(target_os = "linux")]
+impl KeySender {
+    pub fn new() -> Result<Self, SendError> {
+        Ok(Self {
+            inner: platform_impl::KeySender::new(None)?,
+        })
+    }
+
+    /// Like [`KeySender::new`], with keys looked up in `layout` rather than the active one.
+    pub fn with_layout(layout: Layout) -> Result<Self, SendError> {
+        Ok(Self {
+            inner: platform_impl::KeySender::new(Some(layout.inner))?,
+        })
+    }
+
+    /// Presses and releases `key` while holding `modifiers`.
+    ///
+    /// Any extra modifiers the layout needs to produce `key` (e.g. shift for `"A"`) are held as well.
+    pub fn send(&self, key: &Key, modifiers: Modifiers) -> Result<(), SendError> {
+        self.inner.send(key, modifiers)
+    }
+
+    /// Presses `key` without releasing it.
+    ///
+    /// **Note: Modifiers are not applied, they must be pressed as keys of their own.**
+    pub fn press(&self, key: &Key) -> Result<(), SendError> {
+        self.inner.set_pressed(key, true)
+    }
+
+    /// Releases a key previously pressed with [`KeySender::press`].
+    pub fn release(&self, key: &Key) -> Result<(), SendError> {
+        self.inner.set_pressed(key, false)
+    }
+
+    /// Types every character of `text` in order.
+    ///
+    /// Fails on the first character that isn't on the active layout.
+    pub fn type_text(&self, text: &str) -> Result<(), SendError> {
+        self.inner.type_text(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn event() -> Event {
+        Event::Release(KeyEvent::test(
+            Key::Character("a".to_string()),
+            Code::KeyA,
+            30,
+            Modifiers::empty(),
+        ))
+    }
+
+    // a listener that isn't attached to a window, which is sent events and detached like one that
+    // is. `window` must be different for each test, as they share `CHANNELS`
+    fn listener(window: u32) -> KeyboardListener {
+        #[cfg(windows)]
+        let handle = -(window as isize);
+        #[cfg(not(windows))]
+        let handle = platform_impl::PlatformWindowHandle::X11(u32::MAX - window);
+
+        let channel = Arc::new(Channel::new());
+
+        CHANNELS
+            .write()
+            .unwrap()
+            .entry(SendSyncRwh(handle))
+            .or_default()
+            .push(Arc::clone(&channel));
+
+        KeyboardListener {
+            inner: Arc::new(AttachedListener {
+                handle,
+                window: Mutex::new(None),
+                channel,
+            }),
+        }
+    }
+
+    #[test]
+    fn code_locations() {
+        assert_eq!(code_location(Code::ShiftLeft), Location::Left);
+        assert_eq!(code_location(Code::ControlRight), Location::Right);
+        assert_eq!(code_location(Code::MetaRight), Location::Right);
+        assert_eq!(code_location(Code::Numpad5), Location::Numpad);
+        assert_eq!(code_location(Code::NumpadEnter), Location::Numpad);
+        assert_eq!(code_location(Code::KeyA), Location::Standard);
+        assert_eq!(code_location(Code::Enter), Location::Standard);
+    }
+
+    #[test]
+    fn poll_drains_queue() {
+        let (sender, receiver) = kanal::unbounded();
+        let (first, second) = (event(), event());
+
+        sender.send(first.clone()).unwrap();
+        sender.send(second.clone()).unwrap();
+
+        assert_eq!(poll_events(&receiver).unwrap(), vec![first, second]);
+        // doesn't block on the empty queue
+        assert_eq!(poll_events(&receiver).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (sender, receiver) = kanal::unbounded();
+
+        assert_eq!(
+            recv_event_timeout(&receiver, Duration::from_millis(10)).unwrap(),
+            None
+        );
+
+        let event = event();
+        sender.send(event.clone()).unwrap();
+
+        assert_eq!(
+            recv_event_timeout(&receiver, Duration::from_millis(10)).unwrap(),
+            Some(event)
+        );
+    }
+
+    #[test]
+    fn closed_after_queued_events() {
+        let channel = Channel::new();
+        let receiver = channel.receiver.clone();
+        let event = event();
+
+        channel.send(event.clone());
+        channel.close();
+
+        assert_eq!(poll_events(&receiver).unwrap(), vec![event]);
+        assert!(matches!(
+            poll_events(&receiver),
+            Err(ReceiveError::ListenerClosed)
+        ));
+        assert!(matches!(
+            recv_event_timeout(&receiver, Duration::from_millis(10)),
+            Err(ReceiveError::ListenerClosed)
+        ));
+    }
+
+    #[test]
+    fn events_end_on_close() {
+        let listener = listener(1);
+        let event = event();
+
+        let events = thread::spawn({
+            let listener = listener.clone();
+            move || listener.events().collect::<Vec<_>>()
+        });
+
+        send_event(listener.inner.handle, event.clone());
+        listener.detach().unwrap();
+
+        assert_eq!(events.join().unwrap(), vec![event]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn stream_ends_on_close() {
+        use futures_util::StreamExt;
+
+        let listener = listener(4);
+        let (first, second) = (event(), event());
+        let mut stream = listener.stream();
+
+        send_event(listener.inner.handle, first.clone());
+        assert_eq!(futures_executor::block_on(stream.next()), Some(first));
+
+        send_event(listener.inner.handle, second.clone());
+        listener.detach().unwrap();
+
+        let rest: Vec<Event> = futures_executor::block_on(stream.collect());
+        assert_eq!(rest, vec![second]);
+    }
+
+    #[test]
+    fn detach_wakes_receivers() {
+        let listener = listener(2);
+        let window = listener.inner.handle;
+        let handle = listener.handle();
+
+        let received = thread::spawn({
+            let listener = listener.clone();
+            move || listener.try_recv(|_| ())
+        });
+
+        // gives the thread time to block
+        thread::sleep(Duration::from_millis(50));
+        handle.detach().unwrap();
+
+        assert!(matches!(
+            received.join().unwrap(),
+            Err(ReceiveError::ListenerClosed)
+        ));
+        assert!(!CHANNELS.read().unwrap().contains_key(&SendSyncRwh(window)));
+
+        // the events of the window don't reach it anymore
+        send_event(window, event());
+        assert!(matches!(listener.poll(), Err(ReceiveError::ListenerClosed)));
+    }
+
+    #[test]
+    fn detach_after_drop() {
+        let listener = listener(3);
+        let handle = listener.handle();
+
+        drop(listener);
+
+        assert!(handle.detach().is_ok());
+        assert!(handle.detach().is_ok());
+    }
 }
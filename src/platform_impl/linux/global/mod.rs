@@ -0,0 +1,444 @@
+// reads keyboards straight from `/dev/input`, which doesn't need a window or a display server
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt::{self, Display};
+use std::io::ErrorKind;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use evdev::{Device, EventType, InputEvent, Key};
+use inotify::{Inotify, WatchMask};
+use kanal::{Receiver, Sender};
+use rustix::event::{poll, PollFd, PollFlags};
+
+use super::translate_key::{get_modifiers, press_text, translate_key};
+use super::xkb::{ComposeState, Context, Keymap, RuleNames, State};
+use super::AttachError;
+use crate::repeat::RepeatTracker;
+use crate::{code_location, Event, KeyEvent, KeyboardState};
+
+const INPUT_DIR: &str = "/dev/input";
+
+fn evdev_error(e: impl Display) -> AttachError {
+    AttachError::Evdev(e.to_string())
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct GlobalKeyboardListener {
+    listener: Arc<EvdevListener>,
+}
+
+impl GlobalKeyboardListener {
+    pub(crate) fn new() -> Result<Self, AttachError> {
+        Ok(Self {
+            listener: Arc::new(EvdevListener::new()?),
+        })
+    }
+
+    pub(crate) fn receiver(&self) -> &Receiver<Event> {
+        &self.listener.receiver
+    }
+
+    pub(crate) fn state(&self) -> Result<KeyboardState, AttachError> {
+        let keyboards = self
+            .listener
+            .keyboards
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?;
+
+        Ok(keyboards.pressed.clone())
+    }
+
+    pub(crate) fn query_state(&self) -> Result<KeyboardState, AttachError> {
+        let keyboards = self
+            .listener
+            .keyboards
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?;
+
+        Ok(keyboards.query())
+    }
+
+    // why the event thread stopped, `None` if it is running or was stopped by dropping the listener
+    pub(crate) fn error(&self) -> Option<AttachError> {
+        self.listener
+            .keyboards
+            .lock()
+            .ok()
+            .and_then(|keyboards| keyboards.error.clone())
+    }
+}
+
+#[derive(Debug)]
+struct EvdevListener {
+    receiver: Receiver<Event>,
+    // shared with the event thread so the devices can be asked which keys are down
+    keyboards: Arc<Mutex<Keyboards>>,
+    // the event thread stops once the other end of this is closed
+    wake: Option<UnixStream>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EvdevListener {
+    fn new() -> Result<Self, AttachError> {
+        let context = Context::new().ok_or_else(|| evdev_error("failed to load libxkbcommon"))?;
+
+        // there is no server to ask for the layout, so the `XKB_DEFAULT_*` variables are used
+        let state = Keymap::from_names(&context, &RuleNames::default())
+            .and_then(State::new)
+            .ok_or_else(|| evdev_error("failed to compile keymap"))?;
+
+        // watch before enumerating so a keyboard plugged in between the two isn't missed
+        let inotify = Inotify::init().map_err(evdev_error)?;
+        inotify
+            .watches()
+            .add(INPUT_DIR, WatchMask::CREATE | WatchMask::ATTRIB)
+            .map_err(evdev_error)?;
+
+        let (sender, receiver) = kanal::unbounded();
+        let mut keyboards = Keyboards {
+            devices: HashMap::new(),
+            held: HashMap::new(),
+            state,
+            compose: ComposeState::from_locale(&context),
+            repeats: RepeatTracker::new(),
+            pressed: KeyboardState::default(),
+            sender: Some(sender),
+            error: None,
+        };
+
+        let mut denied = None;
+
+        for entry in std::fs::read_dir(INPUT_DIR).map_err(evdev_error)? {
+            let path = entry.map_err(evdev_error)?.path();
+
+            if let Err(e) = keyboards.open(&path) {
+                if e.kind() == ErrorKind::PermissionDenied {
+                    denied = Some(path);
+                }
+            }
+        }
+
+        if keyboards.devices.is_empty() {
+            if let Some(path) = denied {
+                return Err(evdev_error(format!(
+                    "permission denied opening {}, the user needs to be able to read {INPUT_DIR}",
+                    path.display()
+                )));
+            }
+        }
+
+        let keyboards = Arc::new(Mutex::new(keyboards));
+
+        let (wake, wake_other) = UnixStream::pair().map_err(evdev_error)?;
+        let thread = {
+            let keyboards = keyboards.clone();
+            thread::spawn(move || event_loop(keyboards, inotify, wake_other))
+        };
+
+        Ok(Self {
+            receiver,
+            keyboards,
+            wake: Some(wake),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for EvdevListener {
+    fn drop(&mut self) {
+        drop(self.wake.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn event_loop(keyboards: Arc<Mutex<Keyboards>>, inotify: Inotify, wake: UnixStream) {
+    let result = read_events(&keyboards, inotify, wake);
+
+    let mut keyboards = keyboards.lock().expect("poisoned keyboards");
+
+    // receivers get the events sent so far, then see that the listener closed
+    keyboards.sender = None;
+    keyboards.error = result.err();
+}
+
+// returns once the listener is dropped
+fn read_events(
+    keyboards: &Mutex<Keyboards>,
+    mut inotify: Inotify,
+    wake: UnixStream,
+) -> Result<(), AttachError> {
+    let mut buffer = [0; 1024];
+
+    loop {
+        let (paths, device_fds): (Vec<_>, Vec<_>) = keyboards
+            .lock()
+            .expect("poisoned keyboards")
+            .devices
+            .iter()
+            .map(|(path, device)| {
+                // SAFETY: devices are only closed after the fds have been dropped
+                let fd = unsafe { BorrowedFd::borrow_raw(device.as_raw_fd()) };
+
+                (path.clone(), PollFd::from_borrowed_fd(fd, PollFlags::IN))
+            })
+            .unzip();
+
+        let mut fds = vec![
+            PollFd::new(&wake, PollFlags::IN),
+            PollFd::new(&inotify, PollFlags::IN),
+        ];
+        fds.extend(device_fds);
+
+        match poll(&mut fds, -1) {
+            Ok(_) => (),
+            Err(rustix::io::Errno::INTR) => continue,
+            Err(e) => return Err(evdev_error(e)),
+        }
+
+        if !fds[0].revents().is_empty() {
+            return Ok(());
+        }
+
+        let hotplug = !fds[1].revents().is_empty();
+        let ready: Vec<PathBuf> = paths
+            .into_iter()
+            .zip(&fds[2..])
+            .filter(|(_, fd)| !fd.revents().is_empty())
+            .map(|(path, _)| path)
+            .collect();
+
+        drop(fds);
+
+        let mut keyboards = keyboards.lock().expect("poisoned keyboards");
+
+        if hotplug {
+            let events = inotify.read_events(&mut buffer).map_err(evdev_error)?;
+
+            // the node may not be readable until udev has set its permissions,
+            // which is why attribute changes are retried as well
+            let created: Vec<PathBuf> = events
+                .filter_map(|event| event.name.map(|name| Path::new(INPUT_DIR).join(name)))
+                .collect();
+
+            for path in created {
+                let _ = keyboards.open(&path);
+            }
+        }
+
+        for path in ready {
+            keyboards.read(&path);
+        }
+    }
+}
+
+struct Keyboards {
+    devices: HashMap<PathBuf, Device>,
+    // the keys held down on each keyboard, by evdev keycode
+    held: HashMap<PathBuf, HashSet<u16>>,
+
+    // shared by every keyboard, the same as a display server would
+    state: State,
+    // `None` if the locale has no compose table
+    compose: Option<ComposeState>,
+
+    repeats: RepeatTracker<u32>,
+    pressed: KeyboardState,
+    // taken once the event thread stops
+    sender: Option<Sender<Event>>,
+    error: Option<AttachError>,
+}
+
+impl Keyboards {
+    fn open(&mut self, path: &Path) -> std::io::Result<()> {
+        let is_event_node = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| name.starts_with("event"));
+
+        if !is_event_node || self.devices.contains_key(path) {
+            return Ok(());
+        }
+
+        let device = Device::open(path)?;
+
+        if !is_keyboard(&device) {
+            return Ok(());
+        }
+
+        // SAFETY: the device outlives the borrow
+        let fd = unsafe { BorrowedFd::borrow_raw(device.as_raw_fd()) };
+        rustix::io::ioctl_fionbio(fd, true)?;
+
+        self.devices.insert(path.to_path_buf(), device);
+
+        Ok(())
+    }
+
+    fn read(&mut self, path: &Path) {
+        let Some(device) = self.devices.get_mut(path) else {
+            return;
+        };
+
+        let events: Vec<InputEvent> = match device.fetch_events().map(Iterator::collect) {
+            Ok(events) => events,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+            // most likely `ENODEV` because the keyboard was unplugged
+            Err(_) => {
+                self.unplug(path);
+                return;
+            },
+        };
+
+        for event in events {
+            if event.event_type() != EventType::KEY || is_button(event.code()) {
+                continue;
+            }
+
+            let held = self.held.entry(path.to_path_buf()).or_default();
+
+            match event.value() {
+                0 => held.remove(&event.code()),
+                _ => held.insert(event.code()),
+            };
+
+            self.key(event.code(), event.value(), event.timestamp(), false);
+        }
+    }
+
+    // the keys held down on an unplugged keyboard are never released, so they are released for it
+    fn unplug(&mut self, path: &Path) {
+        self.devices.remove(path);
+
+        for code in self.held.remove(path).unwrap_or_default() {
+            // still held down on another keyboard
+            if self.held.values().any(|held| held.contains(&code)) {
+                continue;
+            }
+
+            self.key(code, 0, SystemTime::now(), true);
+        }
+    }
+
+    // `synthetic` for releases that weren't read from a keyboard
+    fn key(&mut self, code: u16, value: i32, timestamp: SystemTime, synthetic: bool) {
+        // xkb keycodes are evdev keycodes offset by 8
+        let keycode = code as u32 + 8;
+
+        // translated before updating the state so a modifier doesn't apply to its own press
+        let mut key = KeyEvent {
+            synthetic,
+            ..self.key_event(keycode, timestamp)
+        };
+
+        if value != 0 {
+            press_text(&self.state, self.compose.as_mut(), &mut key);
+        }
+
+        let event = match value {
+            0 => {
+                self.repeats.release(&keycode);
+                self.state.update_key(keycode, false);
+
+                Event::Release(key)
+            },
+            // 1 is a press, 2 is a repeat generated by the kernel
+            _ => {
+                if value == 1 {
+                    // a release may have been missed while the device was being opened
+                    self.repeats.release(&keycode);
+                    self.state.update_key(keycode, true);
+                }
+
+                Event::Press {
+                    key,
+                    repeat_count: self.repeats.press(keycode),
+                }
+            },
+        };
+
+        // the state has the key applied by now, which is what tells if a lock was turned on
+        let modifiers = Event::ModifiersChanged(get_modifiers(&self.state));
+
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        for event in [event, modifiers] {
+            for event in self.pressed.apply(event) {
+                let _ = sender.send(event);
+            }
+        }
+    }
+
+    fn key_event(&self, keycode: u32, timestamp: SystemTime) -> KeyEvent {
+        let (key, code, raw_key_event_data) = translate_key(&self.state, keycode);
+
+        KeyEvent {
+            key,
+            text: None,
+            composed: None,
+            code,
+            location: code_location(code),
+            modifiers: get_modifiers(&self.state),
+            timestamp,
+            synthetic: false,
+            raw: raw_key_event_data,
+        }
+    }
+
+    // asks every keyboard which of its keys are down
+    fn query(&self) -> KeyboardState {
+        let mut keys = Vec::new();
+
+        for device in self.devices.values() {
+            // the keyboard may have just been unplugged
+            let Ok(held) = device.get_key_state() else {
+                continue;
+            };
+
+            for key in held.iter().filter(|key| !is_button(key.code())) {
+                keys.push(self.key_event(key.code() as u32 + 8, SystemTime::now()));
+            }
+        }
+
+        KeyboardState::new(keys, get_modifiers(&self.state))
+    }
+}
+
+// devices aren't `Debug`, so only their paths are shown
+impl fmt::Debug for Keyboards {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyboards")
+            .field("devices", &self.devices.keys().collect::<Vec<_>>())
+            .field("pressed", &self.pressed)
+            .finish_non_exhaustive()
+    }
+}
+
+// mice and gamepads also report their buttons as keys
+fn is_keyboard(device: &Device) -> bool {
+    device.supported_keys().is_some_and(|keys| {
+        [
+            Key::KEY_A,
+            Key::KEY_ENTER,
+            Key::KEY_VOLUMEUP,
+            Key::KEY_PLAYPAUSE,
+        ]
+        .into_iter()
+        .any(|key| keys.contains(key))
+    })
+}
+
+// buttons on keyboards with a built in touchpad or trackpoint
+fn is_button(code: u16) -> bool {
+    (Key::BTN_0.code()..Key::KEY_OK.code()).contains(&code)
+        || code >= Key::BTN_TRIGGER_HAPPY1.code()
+}
@@ -0,0 +1,23 @@
+use std::fmt::{self, Display};
+
+use xkeysym::Keysym;
+
+use crate::{Key, KeyEvent};
+
+impl Display for KeyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            // this displays the keysym that was looked up with the modifiers of the keypress
+            // which is unlike the character contained in Key::Character as that is looked up without any modifiers
+            Key::Character(_) => match (&self.text, Keysym::new(self.raw.keysym).key_char()) {
+                // includes the text of a finished compose sequence
+                (Some(text), _) => write!(f, "{text}"),
+                (None, Some(c)) if !c.is_control() => write!(f, "{c}"),
+                _ => write!(f, ""),
+            },
+            key => {
+                write!(f, "{}", key)
+            },
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::env;
+
+use super::layout::Layout;
+use super::translate_key::find_key;
+use super::uinput::UinputSender;
+use super::x11::XTestSender;
+use super::xkb::Keymap;
+use super::{KeySender, SendError, SenderBackend};
+use crate::{Key, Modifiers};
+
+// the keys held for each modifier, in the order they are pressed
+const MODIFIER_KEYS: [(Modifiers, Key); 5] = [
+    (Modifiers::CONTROL, Key::Control),
+    (Modifiers::ALT, Key::Alt),
+    (Modifiers::SHIFT, Key::Shift),
+    (Modifiers::SUPER.union(Modifiers::META), Key::Super),
+    (Modifiers::ALT_GRAPH, Key::AltGraph),
+];
+
+impl KeySender {
+    // keys are looked up in `layout` if there is one, otherwise in the active layout
+    pub(crate) fn new(layout: Option<Layout>) -> Result<Self, SendError> {
+        // XTest only reaches X11 clients, so it isn't used on wayland even if xwayland is running
+        let backend =
+            if env::var_os("WAYLAND_DISPLAY").is_none() && env::var_os("DISPLAY").is_some() {
+                SenderBackend::X11(Box::new(XTestSender::connect()?))
+            } else {
+                SenderBackend::Uinput(UinputSender::new()?)
+            };
+
+        Ok(Self { backend, layout })
+    }
+
+    pub(crate) fn send(&self, key: &Key, modifiers: Modifiers) -> Result<(), SendError> {
+        let (keymap, layout) = self.keymap()?;
+        let (keycode, level_modifiers) =
+            find_key(&keymap, layout, key).ok_or_else(|| SendError::UnmappedKey(key.clone()))?;
+
+        let held = modifier_keycodes(&keymap, layout, modifiers | level_modifiers)?;
+
+        for modifier in &held {
+            self.key(*modifier, true)?;
+        }
+
+        self.key(keycode, true)?;
+        self.key(keycode, false)?;
+
+        for modifier in held.iter().rev() {
+            self.key(*modifier, false)?;
+        }
+
+        self.flush()
+    }
+
+    pub(crate) fn set_pressed(&self, key: &Key, pressed: bool) -> Result<(), SendError> {
+        let (keymap, layout) = self.keymap()?;
+        let (keycode, _) =
+            find_key(&keymap, layout, key).ok_or_else(|| SendError::UnmappedKey(key.clone()))?;
+
+        self.key(keycode, pressed)?;
+        self.flush()
+    }
+
+    pub(crate) fn type_text(&self, text: &str) -> Result<(), SendError> {
+        for c in text.chars() {
+            let key = match c {
+                '\n' | '\r' => Key::Enter,
+                '\t' => Key::Tab,
+                '\u{8}' => Key::Backspace,
+                c => Key::Character(c.to_string()),
+            };
+
+            self.send(&key, Modifiers::empty())?;
+        }
+
+        Ok(())
+    }
+
+    fn keymap(&self) -> Result<(Keymap, u32), SendError> {
+        if let Some(layout) = &self.layout {
+            return Ok(layout.keymap());
+        }
+
+        match &self.backend {
+            SenderBackend::X11(x11) => x11.keymap(),
+            SenderBackend::Uinput(uinput) => uinput.keymap(),
+        }
+    }
+
+    fn key(&self, keycode: u32, pressed: bool) -> Result<(), SendError> {
+        match &self.backend {
+            SenderBackend::X11(x11) => x11.key(keycode, pressed),
+            SenderBackend::Uinput(uinput) => uinput.key(keycode, pressed),
+        }
+    }
+
+    fn flush(&self) -> Result<(), SendError> {
+        match &self.backend {
+            SenderBackend::X11(x11) => x11.flush(),
+            SenderBackend::Uinput(uinput) => uinput.flush(),
+        }
+    }
+}
+
+fn modifier_keycodes(
+    keymap: &Keymap,
+    layout: u32,
+    modifiers: Modifiers,
+) -> Result<Vec<u32>, SendError> {
+    MODIFIER_KEYS
+        .into_iter()
+        .filter(|(modifier, _)| modifiers.intersects(*modifier))
+        .map(|(_, key)| {
+            find_key(keymap, layout, &key)
+                .map(|(keycode, _)| keycode)
+                .ok_or(SendError::UnmappedKey(key))
+        })
+        .collect()
+}
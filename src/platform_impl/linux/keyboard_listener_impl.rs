@@ -0,0 +1,95 @@
+use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+
+use super::wayland::WaylandListener;
+use super::x11::{X11Display, X11Listener};
+use super::Backend;
+use crate::platform_impl::{KeyboardListener, PlatformWindowHandle};
+use crate::{AttachError, KeyboardState, LayoutInfo, ListenerError};
+
+// the window `raw_window_handle` is attached to, without connecting to it
+pub(crate) fn window_handle(
+    raw_window_handle: RawWindowHandle,
+    raw_display_handle: RawDisplayHandle,
+) -> Result<PlatformWindowHandle, ListenerError> {
+    let x11_display = matches!(
+        raw_display_handle,
+        RawDisplayHandle::Xlib(_) | RawDisplayHandle::Xcb(_)
+    );
+
+    match (raw_window_handle, raw_display_handle) {
+        (RawWindowHandle::Xlib(h), _) if x11_display => {
+            Ok(PlatformWindowHandle::X11(h.window as u32))
+        },
+        (RawWindowHandle::Xcb(h), _) if x11_display => {
+            Ok(PlatformWindowHandle::X11(h.window.get()))
+        },
+        (RawWindowHandle::Wayland(h), RawDisplayHandle::Wayland(_)) => {
+            Ok(PlatformWindowHandle::Wayland(h.surface.as_ptr() as usize))
+        },
+        _ => Err(ListenerError::InvalidHandle),
+    }
+}
+
+impl KeyboardListener {
+    pub(crate) fn from_raw_window_handle(
+        raw_window_handle: RawWindowHandle,
+        raw_display_handle: RawDisplayHandle,
+        text_input: bool,
+    ) -> Result<Self, ListenerError> {
+        let backend = match (
+            window_handle(raw_window_handle, raw_display_handle)?,
+            raw_display_handle,
+        ) {
+            (PlatformWindowHandle::X11(window), _) => Backend::X11(
+                X11Display::from_handle(raw_display_handle)
+                    .and_then(|display| X11Listener::connect(window, display))
+                    .map_err(ListenerError::AttachError)?,
+            ),
+            (PlatformWindowHandle::Wayland(surface), RawDisplayHandle::Wayland(d)) => {
+                Backend::Wayland(
+                    WaylandListener::connect(d.display.as_ptr() as usize, surface, text_input)
+                        .map_err(ListenerError::AttachError)?,
+                )
+            },
+            _ => return Err(ListenerError::InvalidHandle),
+        };
+
+        Ok(Self { backend })
+    }
+
+    pub(crate) fn attatch(&self) -> Result<(), AttachError> {
+        match &self.backend {
+            Backend::X11(x11) => x11.attatch(),
+            Backend::Wayland(wayland) => wayland.attatch(),
+        }
+    }
+
+    pub(crate) fn detach(&self) -> Result<(), AttachError> {
+        match &self.backend {
+            Backend::X11(x11) => x11.detach(),
+            Backend::Wayland(wayland) => wayland.detach(),
+        }
+    }
+
+    pub(crate) fn query_state(&self) -> Option<KeyboardState> {
+        match &self.backend {
+            Backend::X11(x11) => x11.query_state(),
+            // clients can't ask the compositor which keys are down
+            Backend::Wayland(_) => None,
+        }
+    }
+
+    pub(crate) fn layouts(&self) -> Vec<LayoutInfo> {
+        match &self.backend {
+            Backend::X11(x11) => x11.layouts(),
+            Backend::Wayland(wayland) => wayland.layouts(),
+        }
+    }
+
+    pub(crate) fn active_layout(&self) -> Option<LayoutInfo> {
+        match &self.backend {
+            Backend::X11(x11) => x11.active_layout(),
+            Backend::Wayland(wayland) => wayland.active_layout(),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+#[cfg(target_os = "linux")]
+use super::scancode::keycode_to_code;
+#[cfg(target_os = "linux")]
+use super::translate_key::{find_char, has_base_key, has_code};
+use super::xkb::{Context, Keymap, RuleNames, State};
+#[cfg(target_os = "linux")]
+use crate::{Code, Key, Modifiers};
+use crate::{LayoutId, LayoutInfo};
+
+#[cfg(target_os = "linux")]
+#[derive(Clone, Debug)]
+pub(crate) struct Layout {
+    keymap: Keymap,
+    // the index of the layout in the keymap, which can have several
+    group: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl Layout {
+    pub(crate) fn from_names(layout: &str, variant: &str) -> Option<Self> {
+        let context = Context::new()?;
+        let names = RuleNames {
+            layout: layout.to_string(),
+            variant: variant.to_string(),
+            ..Default::default()
+        };
+
+        Some(Self {
+            keymap: Keymap::from_names(&context, &names)?,
+            group: 0,
+        })
+    }
+
+    pub(crate) fn has_key(&self, key: &Key) -> bool {
+        has_base_key(&self.keymap, self.group, key)
+    }
+
+    pub(crate) fn has_code(&self, code: Code) -> bool {
+        has_code(&self.keymap, self.group, code)
+    }
+
+    pub(crate) fn keymap(&self) -> (Keymap, u32) {
+        (self.keymap.clone(), self.group)
+    }
+
+    pub(crate) fn find_key_for(&self, c: char) -> Option<(Code, Modifiers)> {
+        let (keycode, modifiers) = find_char(&self.keymap, self.group, c)?;
+
+        Some((keycode_to_code(keycode), modifiers))
+    }
+}
+
+// the layouts of a listener's keymap, and which of them is active
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Layouts {
+    installed: Vec<LayoutInfo>,
+    active: Option<LayoutInfo>,
+}
+
+impl Layouts {
+    // `names` are the names the keymap was compiled from, which wayland doesn't send.
+    // the active layout is kept, so `update` tells if the new keymap changed it
+    pub(crate) fn set_keymap(&mut self, keymap: &Keymap, names: Option<&RuleNames>) {
+        let names = names.cloned().unwrap_or_default();
+
+        // one entry for each layout, e.g. `us,de` and `,nodeadkeys`
+        let nth = |list: &str, i: usize| list.split(',').nth(i).unwrap_or_default().to_string();
+
+        self.installed = keymap
+            .layout_names()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let (layout, variant) = (nth(&names.layout, i), nth(&names.variant, i));
+
+                LayoutInfo {
+                    name: if name.is_empty() {
+                        layout.clone()
+                    } else {
+                        name
+                    },
+                    language: language_tag(&layout).map(str::to_string),
+                    id: LayoutId::Xkb {
+                        rules: names.rules.clone(),
+                        model: names.model.clone(),
+                        layout,
+                        variant,
+                        options: names.options.clone(),
+                    },
+                }
+            })
+            .collect();
+    }
+
+    pub(crate) fn installed(&self) -> Vec<LayoutInfo> {
+        self.installed.clone()
+    }
+
+    pub(crate) fn active(&self) -> Option<LayoutInfo> {
+        self.active.clone()
+    }
+
+    // updates the active layout from `state`, returns it if it changed
+    pub(crate) fn update(&mut self, state: &State) -> Option<LayoutInfo> {
+        let active = self.installed.get(state.layout() as usize).cloned();
+
+        if active == self.active {
+            return None;
+        }
+
+        self.active.clone_from(&active);
+
+        active
+    }
+}
+
+// xkb layouts are mostly named after a country rather than a language,
+// so only the common ones are known
+fn language_tag(layout: &str) -> Option<&'static str> {
+    let tag = match layout {
+        "ara" => "ar",
+        "at" => "de-AT",
+        "au" => "en-AU",
+        "be" => "fr-BE",
+        "bg" => "bg-BG",
+        "br" => "pt-BR",
+        "by" => "be-BY",
+        "ca" => "fr-CA",
+        "ch" => "de-CH",
+        "cn" => "zh-CN",
+        "cz" => "cs-CZ",
+        "de" => "de-DE",
+        "dk" => "da-DK",
+        "ee" => "et-EE",
+        "es" => "es-ES",
+        "fi" => "fi-FI",
+        "fr" => "fr-FR",
+        "gb" => "en-GB",
+        "gr" => "el-GR",
+        "hr" => "hr-HR",
+        "hu" => "hu-HU",
+        "ie" => "en-IE",
+        "il" => "he-IL",
+        "in" => "hi-IN",
+        "ir" => "fa-IR",
+        "is" => "is-IS",
+        "it" => "it-IT",
+        "jp" => "ja-JP",
+        "kr" => "ko-KR",
+        "latam" => "es-419",
+        "lt" => "lt-LT",
+        "lv" => "lv-LV",
+        "nl" => "nl-NL",
+        "no" => "nb-NO",
+        "pl" => "pl-PL",
+        "pt" => "pt-PT",
+        "ro" => "ro-RO",
+        "rs" => "sr-RS",
+        "ru" => "ru-RU",
+        "se" => "sv-SE",
+        "si" => "sl-SI",
+        "sk" => "sk-SK",
+        "th" => "th-TH",
+        "tr" => "tr-TR",
+        "tw" => "zh-TW",
+        "ua" => "uk-UA",
+        "us" => "en-US",
+        "vn" => "vi-VN",
+        _ => return None,
+    };
+
+    Some(tag)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn layout(layout: &str, variant: &str) -> Layout {
+        Layout::from_names(layout, variant).expect("failed to compile keymap")
+    }
+
+    #[test]
+    fn find_key_for() {
+        let us = layout("us", "");
+        assert_eq!(us.find_key_for('a'), Some((Code::KeyA, Modifiers::empty())));
+        assert_eq!(us.find_key_for('A'), Some((Code::KeyA, Modifiers::SHIFT)));
+        assert_eq!(us.find_key_for('@'), Some((Code::Digit2, Modifiers::SHIFT)));
+
+        let de = layout("de", "");
+        assert_eq!(de.find_key_for('z'), Some((Code::KeyY, Modifiers::empty())));
+        assert_eq!(
+            de.find_key_for('ü'),
+            Some((Code::BracketLeft, Modifiers::empty()))
+        );
+        assert_eq!(
+            de.find_key_for('@'),
+            Some((Code::KeyQ, Modifiers::ALT_GRAPH))
+        );
+
+        let fr = layout("fr", "");
+        assert_eq!(fr.find_key_for('a'), Some((Code::KeyQ, Modifiers::empty())));
+        assert_eq!(
+            fr.find_key_for('é'),
+            Some((Code::Digit2, Modifiers::empty()))
+        );
+        assert_eq!(fr.find_key_for('1'), Some((Code::Digit1, Modifiers::SHIFT)));
+
+        // the quote is a dead key, which only types itself with AltGr
+        let intl = layout("us", "intl");
+        assert_eq!(
+            intl.find_key_for('\''),
+            Some((Code::Quote, Modifiers::ALT_GRAPH))
+        );
+        assert_eq!(
+            intl.find_key_for('é'),
+            Some((Code::KeyE, Modifiers::ALT_GRAPH))
+        );
+    }
+
+    #[test]
+    fn unproducible_chars() {
+        assert_eq!(layout("us", "").find_key_for('ü'), None);
+        assert_eq!(layout("de", "").find_key_for('字'), None);
+    }
+
+    #[test]
+    fn layout_changes() {
+        let context = Context::new().expect("failed to load libxkbcommon");
+        let names = RuleNames {
+            layout: "us,de".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_names(&context, &names).expect("failed to compile keymap");
+        let mut state = State::new(keymap.clone()).expect("failed to create state");
+
+        let mut layouts = Layouts::default();
+        layouts.set_keymap(&keymap, Some(&names));
+
+        let languages: Vec<_> = layouts
+            .installed()
+            .into_iter()
+            .map(|layout| layout.language)
+            .collect();
+        assert_eq!(
+            languages,
+            [Some("en-US".to_string()), Some("de-DE".to_string())]
+        );
+
+        let us = layouts.update(&state).expect("no active layout");
+        assert_eq!(us.language.as_deref(), Some("en-US"));
+        assert_eq!(layouts.update(&state), None);
+
+        // switching the group
+        state.update_mask(0, 0, 0, 1);
+        let de = layouts.update(&state).expect("the layout didn't change");
+        assert_eq!(de.language.as_deref(), Some("de-DE"));
+        assert_eq!(layouts.active(), Some(de));
+        assert_eq!(layouts.update(&state), None);
+
+        // a new keymap keeps the active layout
+        layouts.set_keymap(&keymap, Some(&names));
+        assert_eq!(layouts.update(&state), None);
+
+        state.update_mask(0, 0, 0, 0);
+        assert_eq!(layouts.update(&state), Some(us));
+    }
+}
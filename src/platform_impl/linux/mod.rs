@@ -0,0 +1,139 @@
+mod key_display;
+#[cfg(target_os = "linux")]
+mod key_sender_impl;
+mod keyboard_listener_impl;
+mod layout;
+mod scancode;
+mod translate_key;
+#[cfg(target_os = "linux")]
+mod uinput;
+mod wayland;
+mod x11;
+mod xkb;
+
+#[cfg(all(feature = "global", target_os = "linux"))]
+mod global;
+
+use std::fmt::{self, Display};
+
+#[cfg(all(feature = "global", target_os = "linux"))]
+pub(crate) use self::global::GlobalKeyboardListener;
+pub(crate) use self::keyboard_listener_impl::window_handle;
+#[cfg(target_os = "linux")]
+pub(crate) use self::layout::Layout;
+#[cfg(target_os = "linux")]
+use self::uinput::UinputSender;
+use self::wayland::WaylandListener;
+use self::x11::X11Listener;
+#[cfg(target_os = "linux")]
+use self::x11::XTestSender;
+pub(crate) use crate::send_event;
+#[cfg(target_os = "linux")]
+use crate::Key;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum PlatformWindowHandle {
+    // window id
+    X11(u32),
+    // `wl_surface` pointer
+    Wayland(usize),
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum AttachError {
+    X11(String),
+    Wayland(String),
+    #[cfg(all(feature = "global", target_os = "linux"))]
+    Evdev(String),
+    PoisonError,
+}
+
+impl Display for AttachError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachError::X11(e) => write!(f, "failed to attach listener: {e}"),
+            AttachError::Wayland(e) => write!(f, "failed to attach listener: {e}"),
+            #[cfg(all(feature = "global", target_os = "linux"))]
+            AttachError::Evdev(e) => write!(f, "failed to attach listener: {e}"),
+            AttachError::PoisonError => write!(f, "failed to attach listener: poisoned RwLock"),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum SendError {
+    X11(String),
+    Uinput(String),
+    // no key on the active layout produces the key
+    UnmappedKey(Key),
+    PoisonError,
+}
+
+#[cfg(target_os = "linux")]
+impl Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::X11(e) => write!(f, "failed to send key: {e}"),
+            SendError::Uinput(e) => write!(f, "failed to send key: {e}"),
+            SendError::UnmappedKey(key) => {
+                write!(f, "failed to send key: {key} is not on the active layout")
+            },
+            SendError::PoisonError => write!(f, "failed to send key: poisoned Mutex"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct RawKeyEventData {
+    keycode: u32,
+    // keysym produced with the modifiers of the event applied
+    keysym: u32,
+}
+
+impl RawKeyEventData {
+    pub(crate) fn scancode(&self) -> u32 {
+        self.keycode
+    }
+
+    #[cfg(test)]
+    pub(crate) fn test(keycode: u32) -> Self {
+        Self { keycode, keysym: 0 }
+    }
+
+    // the character of the level the modifiers chose, e.g. `+` for shift+`=` on a US layout
+    #[cfg(feature = "hotkeys")]
+    pub(crate) fn level_char(&self) -> Option<char> {
+        xkeysym::Keysym::new(self.keysym)
+            .key_char()
+            .filter(|c| !c.is_control())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct KeyboardListener {
+    backend: Backend,
+}
+
+#[derive(Debug)]
+enum Backend {
+    X11(X11Listener),
+    Wayland(WaylandListener),
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub(crate) struct KeySender {
+    backend: SenderBackend,
+    layout: Option<Layout>,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+enum SenderBackend {
+    X11(Box<XTestSender>),
+    Uinput(UinputSender),
+}
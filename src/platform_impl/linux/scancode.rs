@@ -0,0 +1,258 @@
+use crate::Code;
+
+// `keycode` is an xkb keycode, which is the evdev keycode + 8
+pub(crate) fn keycode_to_code(keycode: u32) -> Code {
+    evdev_to_code(keycode.saturating_sub(8))
+}
+
+// see: linux/include/uapi/linux/input-event-codes.h
+// and: https://www.w3.org/TR/uievents-code/
+fn evdev_to_code(code: u32) -> Code {
+    match code {
+        // writing system keys
+        41 => Code::Backquote,
+        43 => Code::Backslash,
+        26 => Code::BracketLeft,
+        27 => Code::BracketRight,
+        51 => Code::Comma,
+        11 => Code::Digit0,
+        2 => Code::Digit1,
+        3 => Code::Digit2,
+        4 => Code::Digit3,
+        5 => Code::Digit4,
+        6 => Code::Digit5,
+        7 => Code::Digit6,
+        8 => Code::Digit7,
+        9 => Code::Digit8,
+        10 => Code::Digit9,
+        13 => Code::Equal,
+        86 => Code::IntlBackslash,
+        89 => Code::IntlRo,
+        124 => Code::IntlYen,
+        30 => Code::KeyA,
+        48 => Code::KeyB,
+        46 => Code::KeyC,
+        32 => Code::KeyD,
+        18 => Code::KeyE,
+        33 => Code::KeyF,
+        34 => Code::KeyG,
+        35 => Code::KeyH,
+        23 => Code::KeyI,
+        36 => Code::KeyJ,
+        37 => Code::KeyK,
+        38 => Code::KeyL,
+        50 => Code::KeyM,
+        49 => Code::KeyN,
+        24 => Code::KeyO,
+        25 => Code::KeyP,
+        16 => Code::KeyQ,
+        19 => Code::KeyR,
+        31 => Code::KeyS,
+        20 => Code::KeyT,
+        22 => Code::KeyU,
+        47 => Code::KeyV,
+        17 => Code::KeyW,
+        45 => Code::KeyX,
+        21 => Code::KeyY,
+        44 => Code::KeyZ,
+        12 => Code::Minus,
+        52 => Code::Period,
+        40 => Code::Quote,
+        39 => Code::Semicolon,
+        53 => Code::Slash,
+
+        // functional keys
+        56 => Code::AltLeft,
+        100 => Code::AltRight,
+        14 => Code::Backspace,
+        58 => Code::CapsLock,
+        127 => Code::ContextMenu,
+        29 => Code::ControlLeft,
+        97 => Code::ControlRight,
+        28 => Code::Enter,
+        125 => Code::MetaLeft,
+        126 => Code::MetaRight,
+        42 => Code::ShiftLeft,
+        54 => Code::ShiftRight,
+        57 => Code::Space,
+        15 => Code::Tab,
+        92 => Code::Convert,
+        93 => Code::KanaMode,
+        122 => Code::Lang1,
+        123 => Code::Lang2,
+        90 => Code::Lang3,
+        91 => Code::Lang4,
+        85 => Code::Lang5,
+        94 => Code::NonConvert,
+
+        // control pad
+        111 => Code::Delete,
+        107 => Code::End,
+        138 => Code::Help,
+        102 => Code::Home,
+        110 => Code::Insert,
+        109 => Code::PageDown,
+        104 => Code::PageUp,
+
+        // arrow pad
+        108 => Code::ArrowDown,
+        105 => Code::ArrowLeft,
+        106 => Code::ArrowRight,
+        103 => Code::ArrowUp,
+
+        // numpad
+        69 => Code::NumLock,
+        82 => Code::Numpad0,
+        79 => Code::Numpad1,
+        80 => Code::Numpad2,
+        81 => Code::Numpad3,
+        75 => Code::Numpad4,
+        76 => Code::Numpad5,
+        77 => Code::Numpad6,
+        71 => Code::Numpad7,
+        72 => Code::Numpad8,
+        73 => Code::Numpad9,
+        78 => Code::NumpadAdd,
+        121 => Code::NumpadComma,
+        83 => Code::NumpadDecimal,
+        98 => Code::NumpadDivide,
+        96 => Code::NumpadEnter,
+        117 => Code::NumpadEqual,
+        55 => Code::NumpadMultiply,
+        179 => Code::NumpadParenLeft,
+        180 => Code::NumpadParenRight,
+        74 => Code::NumpadSubtract,
+
+        // function keys
+        1 => Code::Escape,
+        59 => Code::F1,
+        60 => Code::F2,
+        61 => Code::F3,
+        62 => Code::F4,
+        63 => Code::F5,
+        64 => Code::F6,
+        65 => Code::F7,
+        66 => Code::F8,
+        67 => Code::F9,
+        68 => Code::F10,
+        87 => Code::F11,
+        88 => Code::F12,
+        183 => Code::F13,
+        184 => Code::F14,
+        185 => Code::F15,
+        186 => Code::F16,
+        187 => Code::F17,
+        188 => Code::F18,
+        189 => Code::F19,
+        190 => Code::F20,
+        191 => Code::F21,
+        192 => Code::F22,
+        193 => Code::F23,
+        194 => Code::F24,
+        464 => Code::Fn,
+        99 => Code::PrintScreen,
+        70 => Code::ScrollLock,
+        119 => Code::Pause,
+
+        // media keys
+        158 => Code::BrowserBack,
+        156 => Code::BrowserFavorites,
+        159 => Code::BrowserForward,
+        172 => Code::BrowserHome,
+        173 => Code::BrowserRefresh,
+        217 => Code::BrowserSearch,
+        128 => Code::BrowserStop,
+        161 => Code::Eject,
+        144 => Code::LaunchApp1,
+        140 => Code::LaunchApp2,
+        155 => Code::LaunchMail,
+        164 => Code::MediaPlayPause,
+        226 => Code::MediaSelect,
+        166 => Code::MediaStop,
+        163 => Code::MediaTrackNext,
+        165 => Code::MediaTrackPrevious,
+        208 => Code::MediaFastForward,
+        201 => Code::MediaPause,
+        207 => Code::MediaPlay,
+        167 => Code::MediaRecord,
+        168 => Code::MediaRewind,
+        116 => Code::Power,
+        142 => Code::Sleep,
+        143 => Code::WakeUp,
+        205 => Code::Suspend,
+        114 => Code::AudioVolumeDown,
+        113 => Code::AudioVolumeMute,
+        115 => Code::AudioVolumeUp,
+        248 => Code::MicrophoneMuteToggle,
+        224 => Code::BrightnessDown,
+        225 => Code::BrightnessUp,
+        120 => Code::ShowAllWindows,
+        231 => Code::MailSend,
+        232 => Code::MailReply,
+        233 => Code::MailForward,
+
+        // legacy editing keys
+        129 => Code::Again,
+        133 => Code::Copy,
+        137 => Code::Cut,
+        136 => Code::Find,
+        134 => Code::Open,
+        135 => Code::Paste,
+        130 => Code::Props,
+        131 => Code::Undo,
+
+        _ => Code::Unidentified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writing_system_keys() {
+        assert_eq!(evdev_to_code(30), Code::KeyA);
+        assert_eq!(evdev_to_code(2), Code::Digit1);
+        assert_eq!(evdev_to_code(11), Code::Digit0);
+        assert_eq!(evdev_to_code(57), Code::Space);
+        assert_eq!(evdev_to_code(28), Code::Enter);
+    }
+
+    // keys that share a scan code with another one on PS/2 keyboards have their own evdev code
+    #[test]
+    fn extended_keys() {
+        assert_eq!(evdev_to_code(29), Code::ControlLeft);
+        assert_eq!(evdev_to_code(97), Code::ControlRight);
+        assert_eq!(evdev_to_code(100), Code::AltRight);
+        assert_eq!(evdev_to_code(125), Code::MetaLeft);
+        assert_eq!(evdev_to_code(102), Code::Home);
+        assert_eq!(evdev_to_code(103), Code::ArrowUp);
+        assert_eq!(evdev_to_code(111), Code::Delete);
+    }
+
+    #[test]
+    fn keypad_keys() {
+        assert_eq!(evdev_to_code(82), Code::Numpad0);
+        assert_eq!(evdev_to_code(83), Code::NumpadDecimal);
+        assert_eq!(evdev_to_code(55), Code::NumpadMultiply);
+        assert_eq!(evdev_to_code(96), Code::NumpadEnter);
+        assert_eq!(evdev_to_code(98), Code::NumpadDivide);
+    }
+
+    #[test]
+    fn unknown_codes() {
+        // `KEY_RESERVED`
+        assert_eq!(evdev_to_code(0), Code::Unidentified);
+        // buttons
+        assert_eq!(evdev_to_code(0x110), Code::Unidentified);
+        assert_eq!(evdev_to_code(u32::MAX), Code::Unidentified);
+    }
+
+    #[test]
+    fn xkb_keycodes() {
+        assert_eq!(keycode_to_code(38), Code::KeyA);
+        assert_eq!(keycode_to_code(104), Code::NumpadEnter);
+        // below the offset
+        assert_eq!(keycode_to_code(0), Code::Unidentified);
+    }
+}
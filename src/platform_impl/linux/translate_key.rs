@@ -0,0 +1,652 @@
+use xkbcommon_dl::{
+    XKB_MOD_NAME_ALT, XKB_MOD_NAME_CAPS, XKB_MOD_NAME_CTRL, XKB_MOD_NAME_LOGO, XKB_MOD_NAME_NUM,
+    XKB_MOD_NAME_SHIFT,
+};
+use xkeysym::Keysym;
+
+use super::scancode::keycode_to_code;
+use super::xkb::{Compose, ComposeState, Keymap, State};
+use super::RawKeyEventData;
+use crate::{Code, Key, KeyEvent, Modifiers, SmolStr};
+
+pub(crate) fn get_modifiers(state: &State) -> Modifiers {
+    let mut modifiers = Modifiers::empty();
+
+    if state.mod_name_is_active(XKB_MOD_NAME_ALT) {
+        modifiers.insert(Modifiers::ALT);
+    }
+
+    if state.mod_name_is_active(XKB_MOD_NAME_CTRL) {
+        modifiers.insert(Modifiers::CONTROL);
+    }
+
+    if state.mod_name_is_active(XKB_MOD_NAME_SHIFT) {
+        modifiers.insert(Modifiers::SHIFT);
+    }
+
+    if state.mod_name_is_active(XKB_MOD_NAME_LOGO) {
+        // same as windows, both are set
+        modifiers.insert(Modifiers::SUPER);
+        modifiers.insert(Modifiers::META);
+    }
+
+    if state.level3_is_active() {
+        modifiers.insert(Modifiers::ALT_GRAPH);
+    }
+
+    if state.mod_name_is_active(XKB_MOD_NAME_CAPS) {
+        modifiers.insert(Modifiers::CAPS_LOCK);
+    }
+
+    if state.mod_name_is_active(XKB_MOD_NAME_NUM) {
+        modifiers.insert(Modifiers::NUM_LOCK);
+    }
+
+    if state.scroll_lock_is_active() {
+        modifiers.insert(Modifiers::SCROLL_LOCK);
+    }
+
+    modifiers
+}
+
+// `keycode` is an xkb keycode, which is the evdev keycode + 8
+pub(crate) fn translate_key(state: &State, keycode: u32) -> (Key, Code, RawKeyEventData) {
+    let keysym = state.key_get_one_sym(keycode);
+
+    // like on windows, the key is looked up without any modifiers, which is the first level of
+    // the active layout. the keypad is the exception as num lock decides if it is a digit or not
+    let base_keysym = if keysym.is_keypad_key() {
+        keysym
+    } else {
+        state
+            .keymap()
+            .key_get_sym_by_level(keycode, state.key_get_layout(keycode), 0)
+    };
+
+    (
+        keysym_to_key(base_keysym),
+        keycode_to_code(keycode),
+        RawKeyEventData {
+            keycode,
+            keysym: keysym.raw(),
+        },
+    )
+}
+
+// sets the text typed by pressing `key` with the modifiers of `state`. keys that are part of a dead
+// key or compose sequence type nothing until the last one, which types the text of the sequence.
+// a key that isn't part of the sequence types the dead keys before it along with its own text
+pub(crate) fn press_text(state: &State, compose: Option<&mut ComposeState>, key: &mut KeyEvent) {
+    let text = state.key_get_utf8(key.raw.keycode);
+
+    // e.g. ctrl+a is `\u{1}`
+    key.text =
+        Some(SmolStr::from(text)).filter(|t| !t.is_empty() && !t.chars().any(char::is_control));
+
+    match compose.map(|compose| compose.feed(Keysym::new(key.raw.keysym))) {
+        None | Some(Compose::Ignored) => (),
+        Some(Compose::Composing) => key.text = None,
+        Some(Compose::Composed(text)) => {
+            key.text = Some(SmolStr::from(text)).filter(|t| !t.is_empty());
+            key.composed = key.text.clone();
+        },
+        Some(Compose::Cancelled(dead_keys)) => {
+            let text = dead_keys + key.text.as_deref().unwrap_or_default();
+            key.text = Some(SmolStr::from(text)).filter(|t| !t.is_empty());
+        },
+    }
+}
+
+// the modifiers tried when looking up a key, in order of preference
+const LEVEL_MODIFIERS: [Modifiers; 4] = [
+    Modifiers::empty(),
+    Modifiers::SHIFT,
+    Modifiers::ALT_GRAPH,
+    Modifiers::SHIFT.union(Modifiers::ALT_GRAPH),
+];
+
+// the reverse of `translate_key`, finds a keycode that produces `key` in the given layout along
+// with the modifiers that have to be held for it. `None` if the layout doesn't have the key
+pub(crate) fn find_key(keymap: &Keymap, layout: u32, key: &Key) -> Option<(u32, Modifiers)> {
+    // X11 keycodes are a single byte, keys above that can't be sent anyway
+    let keycodes = keymap.min_keycode()..=keymap.max_keycode().min(u8::MAX as u32);
+
+    find_keysym(keymap, layout, keycodes, |keysym| {
+        keysym_to_key(keysym) == *key
+    })
+}
+
+// finds a keycode that types `c` in the given layout, like `find_key`. dead keys don't count as
+// they only type something with the next key
+pub(crate) fn find_char(keymap: &Keymap, layout: u32, c: char) -> Option<(u32, Modifiers)> {
+    // keymaps also bind keysyms such as `EuroSign` to keycodes that no keyboard has a key for
+    let keycodes = (keymap.min_keycode()..=keymap.max_keycode())
+        .filter(|keycode| keycode_to_code(*keycode) != Code::Unidentified);
+
+    find_keysym(keymap, layout, keycodes, |keysym| {
+        keysym.key_char() == Some(c)
+    })
+}
+
+fn find_keysym(
+    keymap: &Keymap,
+    layout: u32,
+    keycodes: impl Iterator<Item = u32> + Clone,
+    matches: impl Fn(Keysym) -> bool,
+) -> Option<(u32, Modifiers)> {
+    let mut state = State::new(keymap.clone())?;
+
+    for modifiers in LEVEL_MODIFIERS {
+        state.update_mask(modifiers_mask(&state, modifiers), 0, 0, layout);
+
+        let keycode = keycodes
+            .clone()
+            .find(|keycode| matches(state.key_get_one_sym(*keycode)));
+
+        if let Some(keycode) = keycode {
+            return Some((keycode, modifiers));
+        }
+    }
+
+    None
+}
+
+// whether a key of the layout is `key` without any modifiers, which is how `translate_key`
+// reports it
+pub(crate) fn has_base_key(keymap: &Keymap, layout: u32, key: &Key) -> bool {
+    (keymap.min_keycode()..=keymap.max_keycode())
+        .any(|keycode| keysym_to_key(keymap.key_get_sym_by_level(keycode, layout, 0)) == *key)
+}
+
+pub(crate) fn has_code(keymap: &Keymap, layout: u32, code: Code) -> bool {
+    (keymap.min_keycode()..=keymap.max_keycode()).any(|keycode| {
+        keycode_to_code(keycode) == code
+            && keymap.key_get_sym_by_level(keycode, layout, 0) != xkeysym::NO_SYMBOL
+    })
+}
+
+// the reverse of `get_modifiers`, lock modifiers are left out
+fn modifiers_mask(state: &State, modifiers: Modifiers) -> u32 {
+    let level3 = if modifiers.contains(Modifiers::ALT_GRAPH) {
+        state.level3_mask()
+    } else {
+        0
+    };
+
+    [
+        (Modifiers::ALT, XKB_MOD_NAME_ALT),
+        (Modifiers::CONTROL, XKB_MOD_NAME_CTRL),
+        (Modifiers::SHIFT, XKB_MOD_NAME_SHIFT),
+        (Modifiers::SUPER | Modifiers::META, XKB_MOD_NAME_LOGO),
+    ]
+    .into_iter()
+    .filter(|(modifier, _)| modifiers.intersects(*modifier))
+    .filter_map(|(_, name)| state.keymap().mod_get_index(name))
+    .fold(level3, |mask, index| mask | 1 << index)
+}
+
+pub(crate) fn keysym_to_key(keysym: Keysym) -> Key {
+    match keysym {
+        // modifiers
+        Keysym::Alt_L => Key::Alt,
+        Keysym::Alt_R => Key::Alt,
+        Keysym::ISO_Level3_Shift => Key::AltGraph,
+        Keysym::ISO_Level3_Latch => Key::AltGraph,
+        Keysym::ISO_Level3_Lock => Key::AltGraph,
+        Keysym::Mode_switch => Key::ModeChange,
+        Keysym::Control_L => Key::Control,
+        Keysym::Control_R => Key::Control,
+        Keysym::Shift_L => Key::Shift,
+        Keysym::Shift_R => Key::Shift,
+        Keysym::Meta_L => Key::Meta,
+        Keysym::Meta_R => Key::Meta,
+        Keysym::Super_L => Key::Super,
+        Keysym::Super_R => Key::Super,
+        Keysym::Hyper_L => Key::Hyper,
+        Keysym::Hyper_R => Key::Hyper,
+        Keysym::XF86_Fn => Key::Fn,
+        Keysym::Caps_Lock => Key::CapsLock,
+        Keysym::Scroll_Lock => Key::ScrollLock,
+        Keysym::Num_Lock => Key::NumLock,
+        Keysym::ISO_Next_Group => Key::GroupNext,
+        Keysym::ISO_Prev_Group => Key::GroupPrevious,
+        Keysym::ISO_First_Group => Key::GroupFirst,
+        Keysym::ISO_Last_Group => Key::GroupLast,
+
+        // whitespace
+        Keysym::Return => Key::Enter,
+        Keysym::KP_Enter => Key::Enter,
+        Keysym::ISO_Enter => Key::Enter,
+        Keysym::_3270_Enter => Key::Enter,
+        Keysym::Tab => Key::Tab,
+        Keysym::KP_Tab => Key::Tab,
+        Keysym::ISO_Left_Tab => Key::Tab,
+
+        // navigation
+        Keysym::Left => Key::ArrowLeft,
+        Keysym::Right => Key::ArrowRight,
+        Keysym::Up => Key::ArrowUp,
+        Keysym::Down => Key::ArrowDown,
+        Keysym::Page_Up => Key::PageUp,
+        Keysym::Page_Down => Key::PageDown,
+        Keysym::End => Key::End,
+        Keysym::Home => Key::Home,
+        Keysym::KP_Left => Key::ArrowLeft,
+        Keysym::KP_Right => Key::ArrowRight,
+        Keysym::KP_Up => Key::ArrowUp,
+        Keysym::KP_Down => Key::ArrowDown,
+        Keysym::KP_Page_Up => Key::PageUp,
+        Keysym::KP_Page_Down => Key::PageDown,
+        Keysym::KP_End => Key::End,
+        Keysym::KP_Home => Key::Home,
+
+        // editing
+        Keysym::BackSpace => Key::Backspace,
+        Keysym::Clear => Key::Clear,
+        Keysym::Delete => Key::Delete,
+        Keysym::KP_Delete => Key::Delete,
+        Keysym::Insert => Key::Insert,
+        Keysym::KP_Insert => Key::Insert,
+        Keysym::Redo => Key::Redo,
+        Keysym::Undo => Key::Undo,
+        Keysym::XF86_Copy => Key::Copy,
+        Keysym::XF86_Cut => Key::Cut,
+        Keysym::XF86_Paste => Key::Paste,
+        Keysym::SUN_Copy => Key::Copy,
+        Keysym::SUN_Cut => Key::Cut,
+        Keysym::SUN_Paste => Key::Paste,
+        Keysym::_3270_EraseEOF => Key::EraseEof,
+        Keysym::_3270_ExSelect => Key::ExSel,
+        Keysym::_3270_CursorSelect => Key::CrSel,
+
+        // ui
+        Keysym::Escape => Key::Escape,
+        Keysym::Cancel => Key::Cancel,
+        Keysym::Execute => Key::Execute,
+        Keysym::Help => Key::Help,
+        Keysym::Pause => Key::Pause,
+        Keysym::Break => Key::Pause,
+        Keysym::Select => Key::Select,
+        Keysym::Menu => Key::ContextMenu,
+        Keysym::Find => Key::Find,
+        Keysym::_3270_Attn => Key::Attn,
+        Keysym::_3270_Play => Key::Play,
+        Keysym::XF86_ZoomIn => Key::ZoomIn,
+        Keysym::XF86_ZoomOut => Key::ZoomOut,
+
+        // device
+        Keysym::Print => Key::PrintScreen,
+        Keysym::Sys_Req => Key::PrintScreen,
+        Keysym::_3270_PrintScreen => Key::PrintScreen,
+        Keysym::XF86_MonBrightnessUp => Key::BrightnessUp,
+        Keysym::XF86_MonBrightnessDown => Key::BrightnessDown,
+        Keysym::SUN_VideoRaiseBrightness => Key::BrightnessUp,
+        Keysym::SUN_VideoLowerBrightness => Key::BrightnessDown,
+        Keysym::XF86_Eject => Key::Eject,
+        Keysym::XF86_LogOff => Key::LogOff,
+        Keysym::XF86_PowerDown => Key::Power,
+        Keysym::XF86_PowerOff => Key::PowerOff,
+        Keysym::XF86_Hibernate => Key::Hibernate,
+        Keysym::XF86_Standby => Key::Standby,
+        Keysym::XF86_Sleep => Key::Standby,
+        Keysym::XF86_Suspend => Key::Standby,
+        Keysym::XF86_WakeUp => Key::WakeUp,
+
+        // ime
+        Keysym::Multi_key => Key::Compose,
+        Keysym::Codeinput => Key::CodeInput,
+        Keysym::SingleCandidate => Key::SingleCandidate,
+        Keysym::MultipleCandidate => Key::AllCandidates,
+        Keysym::PreviousCandidate => Key::PreviousCandidate,
+        Keysym::Henkan_Mode => Key::Convert,
+        Keysym::Muhenkan => Key::NonConvert,
+        Keysym::Kanji => Key::KanjiMode,
+        Keysym::Romaji => Key::Romaji,
+        Keysym::Hiragana => Key::Hiragana,
+        Keysym::Katakana => Key::Katakana,
+        Keysym::Hiragana_Katakana => Key::HiraganaKatakana,
+        Keysym::Zenkaku => Key::Zenkaku,
+        Keysym::Hankaku => Key::Hankaku,
+        Keysym::Zenkaku_Hankaku => Key::ZenkakuHankaku,
+        Keysym::Kana_Lock => Key::KanaMode,
+        Keysym::Kana_Shift => Key::KanaMode,
+        Keysym::Eisu_Shift => Key::Alphanumeric,
+        Keysym::Eisu_toggle => Key::Alphanumeric,
+        Keysym::Hangul => Key::HangulMode,
+        Keysym::Hangul_Hanja => Key::HanjaMode,
+
+        // media
+        Keysym::XF86_AudioPlay => Key::MediaPlayPause,
+        Keysym::XF86_AudioPause => Key::MediaPause,
+        Keysym::XF86_AudioStop => Key::MediaStop,
+        Keysym::XF86_AudioNext => Key::MediaTrackNext,
+        Keysym::XF86_AudioPrev => Key::MediaTrackPrevious,
+        Keysym::XF86_AudioRecord => Key::MediaRecord,
+        Keysym::XF86_AudioRewind => Key::MediaRewind,
+        Keysym::XF86_AudioForward => Key::MediaFastForward,
+        Keysym::XF86_AudioRandomPlay => Key::RandomToggle,
+        Keysym::XF86_AudioCycleTrack => Key::MediaAudioTrack,
+        Keysym::XF86_Close => Key::Close,
+        Keysym::XF86_New => Key::New,
+        Keysym::XF86_Open => Key::Open,
+        Keysym::SUN_Open => Key::Open,
+        Keysym::XF86_Save => Key::Save,
+        Keysym::XF86_Spell => Key::SpellCheck,
+        Keysym::XF86_Reply => Key::MailReply,
+        Keysym::XF86_MailForward => Key::MailForward,
+        Keysym::XF86_Send => Key::MailSend,
+        Keysym::XF86_Subtitle => Key::Subtitle,
+        Keysym::XF86_SplitScreen => Key::SplitScreenToggle,
+        Keysym::XF86_Next_VMode => Key::VideoModeNext,
+        Keysym::XF86_Red => Key::ColorF0Red,
+        Keysym::XF86_Green => Key::ColorF1Green,
+        Keysym::XF86_Yellow => Key::ColorF2Yellow,
+        Keysym::XF86_Blue => Key::ColorF3Blue,
+        Keysym::XF86_Display => Key::DisplaySwap,
+
+        // audio
+        Keysym::XF86_AudioLowerVolume => Key::AudioVolumeDown,
+        Keysym::XF86_AudioRaiseVolume => Key::AudioVolumeUp,
+        Keysym::XF86_AudioMute => Key::AudioVolumeMute,
+        Keysym::SUN_AudioLowerVolume => Key::AudioVolumeDown,
+        Keysym::SUN_AudioRaiseVolume => Key::AudioVolumeUp,
+        Keysym::SUN_AudioMute => Key::AudioVolumeMute,
+        Keysym::XF86_AudioMicMute => Key::MicrophoneVolumeMute,
+
+        // launch
+        Keysym::XF86_Mail => Key::LaunchMail,
+        Keysym::XF86_Calendar => Key::LaunchCalendar,
+        Keysym::XF86_Calculator => Key::LaunchApplication2,
+        // typo in the original keysym name
+        Keysym::XF86_Calculater => Key::LaunchApplication2,
+        Keysym::XF86_MyComputer => Key::LaunchApplication1,
+        Keysym::XF86_Explorer => Key::LaunchApplication1,
+        Keysym::XF86_Launch0 => Key::LaunchApplication1,
+        Keysym::XF86_Launch1 => Key::LaunchApplication2,
+        Keysym::XF86_ScreenSaver => Key::LaunchScreenSaver,
+        Keysym::XF86_WWW => Key::LaunchWebBrowser,
+        Keysym::XF86_Excel => Key::LaunchSpreadsheet,
+        Keysym::XF86_Word => Key::LaunchWordProcessor,
+        Keysym::XF86_Phone => Key::LaunchPhone,
+        Keysym::XF86_Video => Key::LaunchMediaPlayer,
+        Keysym::XF86_Music => Key::LaunchMusicPlayer,
+        Keysym::XF86_WebCam => Key::LaunchWebCam,
+
+        // browser
+        Keysym::XF86_Back => Key::BrowserBack,
+        Keysym::XF86_Forward => Key::BrowserForward,
+        Keysym::XF86_Favorites => Key::BrowserFavorites,
+        Keysym::XF86_MySites => Key::BrowserFavorites,
+        Keysym::XF86_HomePage => Key::BrowserHome,
+        Keysym::XF86_Refresh => Key::BrowserRefresh,
+        Keysym::XF86_Reload => Key::BrowserRefresh,
+        Keysym::XF86_Search => Key::BrowserSearch,
+        Keysym::XF86_Stop => Key::BrowserStop,
+
+        // function
+        Keysym::F1 => Key::F1,
+        Keysym::F2 => Key::F2,
+        Keysym::F3 => Key::F3,
+        Keysym::F4 => Key::F4,
+        Keysym::F5 => Key::F5,
+        Keysym::F6 => Key::F6,
+        Keysym::F7 => Key::F7,
+        Keysym::F8 => Key::F8,
+        Keysym::F9 => Key::F9,
+        Keysym::F10 => Key::F10,
+        Keysym::F11 => Key::F11,
+        Keysym::F12 => Key::F12,
+        Keysym::F13 => Key::F13,
+        Keysym::F14 => Key::F14,
+        Keysym::F15 => Key::F15,
+        Keysym::F16 => Key::F16,
+        Keysym::F17 => Key::F17,
+        Keysym::F18 => Key::F18,
+        Keysym::F19 => Key::F19,
+        Keysym::F20 => Key::F20,
+        Keysym::F21 => Key::F21,
+        Keysym::F22 => Key::F22,
+        Keysym::F23 => Key::F23,
+        Keysym::F24 => Key::F24,
+        Keysym::F25 => Key::F25,
+        Keysym::F26 => Key::F26,
+        Keysym::F27 => Key::F27,
+        Keysym::F28 => Key::F28,
+        Keysym::F29 => Key::F29,
+        Keysym::F30 => Key::F30,
+        Keysym::F31 => Key::F31,
+        Keysym::F32 => Key::F32,
+        Keysym::F33 => Key::F33,
+        Keysym::F34 => Key::F34,
+        Keysym::F35 => Key::F35,
+        Keysym::KP_F1 => Key::F1,
+        Keysym::KP_F2 => Key::F2,
+        Keysym::KP_F3 => Key::F3,
+        Keysym::KP_F4 => Key::F4,
+
+        // dead keys, combined with the next key by `ComposeState`
+        keysym
+            if (Keysym::dead_grave.raw()..=Keysym::dead_longsolidusoverlay.raw())
+                .contains(&keysym.raw()) =>
+        {
+            Key::Dead
+        },
+
+        // printable keysyms
+        keysym => match keysym.key_char() {
+            Some(c) if !c.is_control() => Key::Character(c.to_string()),
+            _ => Key::Unidentified,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform_impl::platform::xkb::{Context, RuleNames};
+    use crate::Location;
+
+    // xkb keycodes, which are evdev keycodes + 8
+    const KEY_GRAVE: u32 = 49;
+    const KEY_MINUS: u32 = 20;
+    const KEY_A: u32 = 38;
+    const KEY_Q: u32 = 24;
+    const KEY_Y: u32 = 29;
+    const KEY_ENTER: u32 = 36;
+    const KEY_KP1: u32 = 87;
+
+    fn state(layout: &str) -> State {
+        let context = Context::new().expect("failed to load libxkbcommon");
+        let names = RuleNames {
+            layout: layout.to_string(),
+            ..Default::default()
+        };
+
+        Keymap::from_names(&context, &names)
+            .and_then(State::new)
+            .expect("failed to compile keymap")
+    }
+
+    fn mask(state: &State, name: &[u8]) -> u32 {
+        1 << state
+            .keymap()
+            .mod_get_index(name)
+            .expect("no such modifier")
+    }
+
+    fn key(state: &State, keycode: u32) -> Key {
+        translate_key(state, keycode).0
+    }
+
+    fn press(state: &State, keycode: u32, modifiers: Modifiers) -> KeyEvent {
+        let (key, code, raw) = translate_key(state, keycode);
+
+        KeyEvent {
+            key,
+            text: None,
+            composed: None,
+            code,
+            location: Location::Standard,
+            modifiers,
+            timestamp: std::time::SystemTime::now(),
+            synthetic: false,
+            raw,
+        }
+    }
+
+    fn type_keys(state: &State, compose: &mut ComposeState, keycodes: &[u32]) -> Vec<KeyEvent> {
+        keycodes
+            .iter()
+            .map(|keycode| {
+                let mut key = press(state, *keycode, Modifiers::empty());
+                press_text(state, Some(compose), &mut key);
+                key
+            })
+            .collect()
+    }
+
+    #[test]
+    fn base_level() {
+        let mut us = state("us");
+        assert_eq!(key(&us, KEY_A), Key::Character("a".to_string()));
+        assert_eq!(key(&us, KEY_ENTER), Key::Enter);
+
+        // the key is the same with shift held, the keysym isn't
+        us.update_mask(mask(&us, XKB_MOD_NAME_SHIFT), 0, 0, 0);
+        let (shifted, code, raw) = translate_key(&us, KEY_A);
+        assert_eq!(shifted, Key::Character("a".to_string()));
+        assert_eq!(code, Code::KeyA);
+        assert_eq!(raw.keysym, Keysym::A.raw());
+
+        // the layout decides the key, not the code
+        let de = state("de");
+        assert_eq!(key(&de, KEY_Y), Key::Character("z".to_string()));
+        assert_eq!(key(&de, KEY_MINUS), Key::Character("ß".to_string()));
+        assert_eq!(translate_key(&de, KEY_Y).1, Code::KeyY);
+    }
+
+    // AltGr is whichever modifier the keymap's `ISO_Level3_Shift` key sets
+    #[test]
+    fn alt_graph() {
+        let mut de = state("de");
+        // `Mod5` on the default keymaps
+        assert_ne!(de.level3_mask() & mask(&de, b"Mod5\0"), 0);
+
+        de.update_mask(de.level3_mask(), 0, 0, 0);
+        assert_eq!(get_modifiers(&de), Modifiers::ALT_GRAPH);
+        assert_eq!(key(&de, KEY_Q), Key::Character("q".to_string()));
+        assert_eq!(de.key_get_utf8(KEY_Q), "@");
+    }
+
+    // num lock decides what the keypad keys are
+    #[test]
+    fn keypad() {
+        let mut state = state("us");
+        assert_eq!(key(&state, KEY_KP1), Key::End);
+
+        state.update_mask(0, 0, mask(&state, XKB_MOD_NAME_NUM), 0);
+        assert_eq!(key(&state, KEY_KP1), Key::Character("1".to_string()));
+        assert_eq!(translate_key(&state, KEY_KP1).1, Code::Numpad1);
+    }
+
+    #[test]
+    fn dead_keys() {
+        let de = state("de");
+        assert_eq!(key(&de, KEY_GRAVE), Key::Dead);
+
+        assert_eq!(keysym_to_key(Keysym::dead_acute), Key::Dead);
+        assert_eq!(keysym_to_key(Keysym::dead_grave), Key::Dead);
+        assert_eq!(keysym_to_key(Keysym::dead_longsolidusoverlay), Key::Dead);
+    }
+
+    #[test]
+    fn compose() {
+        const KEY_E: u32 = 26;
+        const KEY_X: u32 = 53;
+        // `dead_acute` on a German layout
+        const KEY_EQUAL: u32 = 21;
+
+        let de = state("de");
+        let context = Context::new().expect("failed to load libxkbcommon");
+        let Some(mut compose) = ComposeState::new(&context, c"en_US.UTF-8") else {
+            // no compose tables installed
+            return;
+        };
+
+        let text = |keys: Vec<KeyEvent>| {
+            keys.into_iter()
+                .map(|k| (k.text.map(String::from), k.composed.map(String::from)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            text(type_keys(&de, &mut compose, &[KEY_EQUAL, KEY_E])),
+            [(None, None), (Some("é".into()), Some("é".into()))]
+        );
+
+        // typed after the dead key when they can't be combined
+        assert_eq!(
+            text(type_keys(&de, &mut compose, &[KEY_EQUAL, KEY_X])),
+            [(None, None), (Some("´x".into()), None)]
+        );
+        assert_eq!(
+            text(type_keys(&de, &mut compose, &[KEY_X])),
+            [(Some("x".into()), None)]
+        );
+    }
+
+    #[test]
+    fn keysyms() {
+        assert_eq!(keysym_to_key(Keysym::Shift_L), Key::Shift);
+        assert_eq!(keysym_to_key(Keysym::ISO_Level3_Shift), Key::AltGraph);
+        assert_eq!(keysym_to_key(Keysym::Return), Key::Enter);
+        assert_eq!(keysym_to_key(Keysym::KP_Enter), Key::Enter);
+        assert_eq!(
+            keysym_to_key(Keysym::EuroSign),
+            Key::Character("€".to_string())
+        );
+        assert_eq!(keysym_to_key(xkeysym::NO_SYMBOL), Key::Unidentified);
+    }
+
+    // the accelerator is written with the character typed, not with the key
+    #[cfg(feature = "hotkeys")]
+    #[test]
+    fn accelerator_levels() {
+        use crate::Accelerator;
+
+        const KEY_EQUAL: u32 = 21;
+        const KEY_RIGHTBRACE: u32 = 35;
+
+        let plus: Accelerator = "Ctrl++".parse().unwrap();
+        let equal: Accelerator = "Ctrl+=".parse().unwrap();
+
+        // `+` is shift+`=` on a US layout
+        let mut us = state("us");
+        us.update_mask(mask(&us, XKB_MOD_NAME_SHIFT), 0, 0, 0);
+        let shifted = press(&us, KEY_EQUAL, Modifiers::CONTROL | Modifiers::SHIFT);
+        assert!(plus.matches(&shifted));
+        assert!("Ctrl+Shift+Plus"
+            .parse::<Accelerator>()
+            .unwrap()
+            .matches(&shifted));
+        assert!(!equal.matches(&shifted));
+
+        let us = state("us");
+        assert!(!plus.matches(&press(&us, KEY_EQUAL, Modifiers::CONTROL)));
+        assert!(equal.matches(&press(&us, KEY_EQUAL, Modifiers::CONTROL)));
+
+        // and a key of its own on a German one
+        let de = state("de");
+        assert!(plus.matches(&press(&de, KEY_RIGHTBRACE, Modifiers::CONTROL)));
+
+        // shift doesn't make a letter another accelerator
+        let mut us = state("us");
+        us.update_mask(mask(&us, XKB_MOD_NAME_SHIFT), 0, 0, 0);
+        let shifted = press(&us, KEY_A, Modifiers::CONTROL | Modifiers::SHIFT);
+        assert!(!"Ctrl+A".parse::<Accelerator>().unwrap().matches(&shifted));
+        assert!("Ctrl+Shift+A"
+            .parse::<Accelerator>()
+            .unwrap()
+            .matches(&shifted));
+    }
+}
@@ -0,0 +1,87 @@
+// a virtual keyboard created through `/dev/uinput`, which works everywhere the kernel's
+// input devices are read, including wayland compositors and the console
+use std::env;
+use std::fmt::{self, Debug, Display};
+use std::sync::Mutex;
+
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, EventType, InputEvent, Key};
+
+use super::x11::{server_state, xcb_connection, X11Display};
+use super::xkb::{Context, Keymap, RuleNames};
+use super::SendError;
+
+fn uinput_error(e: impl Display) -> SendError {
+    SendError::Uinput(e.to_string())
+}
+
+pub(crate) struct UinputSender {
+    device: Mutex<VirtualDevice>,
+    keymap: Keymap,
+    layout: u32,
+}
+
+impl UinputSender {
+    pub(crate) fn new() -> Result<Self, SendError> {
+        // every code below the first button, which covers every key a keyboard can have
+        let mut keys = AttributeSet::<Key>::new();
+        for code in Key::KEY_ESC.code()..Key::BTN_0.code() {
+            keys.insert(Key::new(code));
+        }
+
+        let device = VirtualDeviceBuilder::new()
+            .and_then(|builder| builder.name("crosskey virtual keyboard").with_keys(&keys))
+            .and_then(|builder| builder.build())
+            .map_err(uinput_error)?;
+
+        // the compositor's layout can't be asked for without a window, but xwayland's keymap is
+        // a copy of it. otherwise the `XKB_DEFAULT_*` variables are used
+        let context = Context::new().ok_or_else(|| uinput_error("failed to load libxkbcommon"))?;
+        let (keymap, layout) = match env::var_os("DISPLAY")
+            .and_then(|_| xcb_connection(&X11Display::default()))
+            .and_then(|conn| server_state(&context, &conn))
+        {
+            Some(state) => (state.keymap().clone(), state.layout()),
+            None => (
+                Keymap::from_names(&context, &RuleNames::default())
+                    .ok_or_else(|| uinput_error("failed to compile keymap"))?,
+                0,
+            ),
+        };
+
+        Ok(Self {
+            device: Mutex::new(device),
+            keymap,
+            layout,
+        })
+    }
+
+    pub(crate) fn keymap(&self) -> Result<(Keymap, u32), SendError> {
+        Ok((self.keymap.clone(), self.layout))
+    }
+
+    pub(crate) fn key(&self, keycode: u32, pressed: bool) -> Result<(), SendError> {
+        // xkb keycodes are evdev keycodes offset by 8
+        let event = InputEvent::new(EventType::KEY, (keycode - 8) as u16, pressed as i32);
+
+        self.device
+            .lock()
+            .map_err(|_| SendError::PoisonError)?
+            .emit(&[event])
+            .map_err(uinput_error)
+    }
+
+    pub(crate) fn flush(&self) -> Result<(), SendError> {
+        // every event is followed by a `SYN_REPORT` already
+        Ok(())
+    }
+}
+
+impl Debug for UinputSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UinputSender")
+            .field("keymap", &self.keymap)
+            .field("layout", &self.layout)
+            .finish_non_exhaustive()
+    }
+}
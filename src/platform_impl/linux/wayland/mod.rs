@@ -0,0 +1,506 @@
+mod text_input;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use rustix::event::{poll, PollFd, PollFlags};
+use wayland_backend::client::Backend;
+use wayland_client::protocol::wl_keyboard::{self, KeyState, KeymapFormat, WlKeyboard};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::protocol::wl_seat::{self, Capability, WlSeat};
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3;
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_v3::ZwpTextInputV3;
+
+use self::text_input::Composition;
+use super::layout::Layouts;
+use super::translate_key::{get_modifiers, press_text, translate_key};
+use super::xkb::{ComposeState, Context, Keymap, State};
+use super::{send_event, AttachError, PlatformWindowHandle};
+use crate::repeat::RepeatTracker;
+use crate::{code_location, Event, KeyEvent, LayoutInfo};
+
+fn wayland_error(e: impl Display) -> AttachError {
+    AttachError::Wayland(e.to_string())
+}
+
+#[derive(Debug)]
+pub(crate) struct WaylandListener {
+    conn: Connection,
+    surface: usize,
+    // whether the compositor's input method is bound
+    text_input: bool,
+    // updated by the event thread whenever the compositor sends a keymap
+    layouts: Arc<Mutex<Layouts>>,
+    // the event thread stops once the other end of this is closed
+    wake: Mutex<Option<UnixStream>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl WaylandListener {
+    pub(crate) fn connect(
+        display: usize,
+        surface: usize,
+        text_input: bool,
+    ) -> Result<Self, AttachError> {
+        // SAFETY: the display is owned by the window, which the caller keeps alive while attached.
+        // the foreign backend uses its own event queue so it doesn't steal events from the window
+        let backend = unsafe { Backend::from_foreign_display(display as *mut _) };
+
+        Ok(Self {
+            conn: Connection::from_backend(backend),
+            surface,
+            text_input,
+            layouts: Arc::new(Mutex::new(Layouts::default())),
+            wake: Mutex::new(None),
+            thread: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn attatch(&self) -> Result<(), AttachError> {
+        let context = Context::new()
+            .ok_or_else(|| AttachError::Wayland("failed to load libxkbcommon".to_string()))?;
+
+        let mut queue = self.conn.new_event_queue();
+        let _registry = self.conn.display().get_registry(&queue.handle(), ());
+
+        let mut state =
+            KeyboardState::new(self.surface, self.text_input, context, self.layouts.clone());
+
+        // one roundtrip for the globals and one for the seat capabilities
+        queue.roundtrip(&mut state).map_err(wayland_error)?;
+
+        if state.seat.is_none() {
+            return Err(AttachError::Wayland("no wl_seat available".to_string()));
+        }
+
+        queue.roundtrip(&mut state).map_err(wayland_error)?;
+
+        let (wake, wake_other) = UnixStream::pair().map_err(wayland_error)?;
+        let thread = thread::spawn(move || event_loop(queue, state, wake_other));
+
+        *self.wake.lock().map_err(|_| AttachError::PoisonError)? = Some(wake);
+        *self.thread.lock().map_err(|_| AttachError::PoisonError)? = Some(thread);
+
+        Ok(())
+    }
+}
+
+impl WaylandListener {
+    pub(crate) fn layouts(&self) -> Vec<LayoutInfo> {
+        self.layouts.lock().expect("poisoned layouts").installed()
+    }
+
+    pub(crate) fn active_layout(&self) -> Option<LayoutInfo> {
+        self.layouts.lock().expect("poisoned layouts").active()
+    }
+
+    pub(crate) fn detach(&self) -> Result<(), AttachError> {
+        drop(
+            self.wake
+                .lock()
+                .map_err(|_| AttachError::PoisonError)?
+                .take(),
+        );
+
+        if let Some(thread) = self
+            .thread
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?
+            .take()
+        {
+            let _ = thread.join();
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WaylandListener {
+    fn drop(&mut self) {
+        let _ = self.detach();
+    }
+}
+
+fn event_loop(mut queue: EventQueue<KeyboardState>, mut state: KeyboardState, wake: UnixStream) {
+    loop {
+        if queue.dispatch_pending(&mut state).is_err() || queue.flush().is_err() {
+            break;
+        }
+
+        // `None` means more events were queued in the meantime
+        let Some(guard) = queue.prepare_read() else {
+            continue;
+        };
+
+        let timeout = state
+            .repeat
+            .as_ref()
+            .map(|repeat| {
+                repeat
+                    .next
+                    .saturating_duration_since(Instant::now())
+                    .as_millis() as i32
+            })
+            .unwrap_or(-1);
+
+        let mut fds = [
+            PollFd::from_borrowed_fd(guard.connection_fd(), PollFlags::IN),
+            PollFd::new(&wake, PollFlags::IN),
+        ];
+
+        match poll(&mut fds, timeout) {
+            Ok(_) => (),
+            Err(rustix::io::Errno::INTR) => continue,
+            Err(_) => break,
+        }
+
+        if !fds[1].revents().is_empty() {
+            break;
+        }
+
+        if !fds[0].revents().is_empty() {
+            // another thread reading the same display isn't an error
+            if let Err(wayland_backend::client::WaylandError::Io(e)) = guard.read() {
+                if e.kind() != std::io::ErrorKind::WouldBlock {
+                    break;
+                }
+            }
+        }
+
+        state.emit_repeats();
+    }
+
+    // the display is the window's, so the objects bound on it would outlive the queue
+    state.destroy();
+    let _ = queue.flush();
+}
+
+struct KeyRepeat {
+    keycode: u32,
+    next: Instant,
+}
+
+struct KeyboardState {
+    surface: usize,
+    focused: bool,
+
+    seat: Option<WlSeat>,
+    keyboard: Option<WlKeyboard>,
+    // input methods are only bound when asked for, as they take over the surface's text input
+    text_input_enabled: bool,
+    // `None` if the compositor doesn't support input methods
+    text_input_manager: Option<ZwpTextInputManagerV3>,
+    text_input: Option<ZwpTextInputV3>,
+    composition: Composition,
+
+    context: Context,
+    state: Option<State>,
+    layouts: Arc<Mutex<Layouts>>,
+    // `None` if the locale has no compose table
+    compose: Option<ComposeState>,
+
+    repeats: RepeatTracker<u32>,
+    // the compositor only sends the repeat rate, repeated presses are created by us
+    repeat: Option<KeyRepeat>,
+    repeat_rate: i32,
+    repeat_delay: Duration,
+}
+
+impl KeyboardState {
+    fn new(
+        surface: usize,
+        text_input_enabled: bool,
+        context: Context,
+        layouts: Arc<Mutex<Layouts>>,
+    ) -> Self {
+        Self {
+            surface,
+            focused: false,
+            seat: None,
+            keyboard: None,
+            text_input_enabled,
+            text_input_manager: None,
+            text_input: None,
+            composition: Composition::default(),
+            compose: ComposeState::from_locale(&context),
+            context,
+            state: None,
+            layouts,
+            repeats: RepeatTracker::new(),
+            repeat: None,
+            // the defaults used by weston when `repeat_info` is not sent
+            repeat_rate: 40,
+            repeat_delay: Duration::from_millis(400),
+        }
+    }
+
+    fn window(&self) -> PlatformWindowHandle {
+        PlatformWindowHandle::Wayland(self.surface)
+    }
+
+    fn key_event(&self, keycode: u32) -> Option<KeyEvent> {
+        let state = self.state.as_ref()?;
+        let (key, code, raw_key_event_data) = translate_key(state, keycode);
+
+        Some(KeyEvent {
+            key,
+            text: None,
+            composed: None,
+            code,
+            location: code_location(code),
+            modifiers: get_modifiers(state),
+            timestamp: SystemTime::now(),
+            synthetic: false,
+            raw: raw_key_event_data,
+        })
+    }
+
+    fn press(&mut self, keycode: u32) {
+        let Some(mut key) = self.key_event(keycode) else {
+            return;
+        };
+        if let Some(state) = &self.state {
+            press_text(state, self.compose.as_mut(), &mut key);
+        }
+
+        let event = Event::Press {
+            key,
+            repeat_count: self.repeats.press(keycode),
+        };
+
+        send_event(self.window(), event);
+    }
+
+    fn release(&mut self, keycode: u32) {
+        self.repeats.release(&keycode);
+
+        if self.repeat.as_ref().is_some_and(|r| r.keycode == keycode) {
+            self.repeat = None;
+        }
+
+        if let Some(key) = self.key_event(keycode) {
+            send_event(self.window(), Event::Release(key));
+        }
+    }
+
+    // releases the objects bound by the listener, so the compositor stops sending events for
+    // them and the input method is no longer enabled on the surface
+    fn destroy(&mut self) {
+        if let Some(text_input) = self.text_input.take() {
+            text_input.disable();
+            text_input.commit();
+            text_input.destroy();
+        }
+
+        if let Some(manager) = self.text_input_manager.take() {
+            manager.destroy();
+        }
+
+        if let Some(keyboard) = self.keyboard.take() {
+            if keyboard.version() >= 3 {
+                keyboard.release();
+            }
+        }
+
+        if let Some(seat) = self.seat.take() {
+            if seat.version() >= 5 {
+                seat.release();
+            }
+        }
+    }
+
+    fn emit_repeats(&mut self) {
+        let now = Instant::now();
+
+        while let Some(repeat) = self.repeat.as_mut().filter(|r| r.next <= now) {
+            repeat.next += Duration::from_secs(1) / self.repeat_rate as u32;
+
+            let keycode = repeat.keycode;
+            self.press(keycode);
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for KeyboardState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            // only the first seat is used
+            if interface == WlSeat::interface().name && state.seat.is_none() {
+                state.seat = Some(registry.bind(name, version.min(7), qh, ()));
+            } else if interface == ZwpTextInputManagerV3::interface().name
+                && state.text_input_enabled
+            {
+                state.text_input_manager = Some(registry.bind(name, 1, qh, ()));
+            }
+
+            // the globals may be announced in any order
+            if let (Some(seat), Some(manager), None) =
+                (&state.seat, &state.text_input_manager, &state.text_input)
+            {
+                state.text_input = Some(manager.get_text_input(seat, qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for KeyboardState {
+    fn event(
+        state: &mut Self,
+        seat: &WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            let has_keyboard = capabilities.contains(Capability::Keyboard);
+
+            if has_keyboard && state.keyboard.is_none() {
+                state.keyboard = Some(seat.get_keyboard(qh, ()));
+            } else if !has_keyboard {
+                if let Some(keyboard) = state.keyboard.take() {
+                    if keyboard.version() >= 3 {
+                        keyboard.release();
+                    }
+                }
+
+                state.repeat = None;
+            }
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for KeyboardState {
+    fn event(
+        state: &mut Self,
+        _: &WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_keyboard::Event::Keymap {
+                format: WEnum::Value(KeymapFormat::XkbV1),
+                fd,
+                size,
+            } => {
+                // the fd may be shared with other clients, so it is read without moving the file offset
+                let mut buffer = vec![0; size as usize];
+
+                if File::from(fd).read_exact_at(&mut buffer, 0).is_ok() {
+                    state.state = Keymap::from_buffer(&state.context, &buffer).and_then(State::new);
+
+                    // the active layout is only known once the modifiers are sent
+                    if let Some(xkb_state) = &state.state {
+                        let mut layouts = state.layouts.lock().expect("poisoned layouts");
+                        layouts.set_keymap(xkb_state.keymap(), None);
+                    }
+                }
+            },
+            wl_keyboard::Event::Enter { surface, .. } => {
+                state.focused = surface.id().as_ptr() as usize == state.surface;
+
+                if state.focused {
+                    send_event(state.window(), Event::FocusGained);
+                }
+            },
+            wl_keyboard::Event::Leave { .. } => {
+                // the releases of keys that are still down go to the surface focused instead
+                state.repeats.clear();
+                state.repeat = None;
+
+                if let Some(compose) = &mut state.compose {
+                    compose.reset();
+                }
+
+                if std::mem::take(&mut state.focused) {
+                    send_event(state.window(), Event::FocusLost);
+                }
+            },
+            wl_keyboard::Event::Key {
+                key,
+                state: WEnum::Value(key_state),
+                ..
+            } if state.focused => {
+                // wayland sends evdev keycodes, xkb keycodes are offset by 8
+                let keycode = key + 8;
+
+                match key_state {
+                    KeyState::Pressed => {
+                        state.press(keycode);
+
+                        let repeats = state
+                            .state
+                            .as_ref()
+                            .is_some_and(|s| s.keymap().key_repeats(keycode));
+
+                        state.repeat = (repeats && state.repeat_rate > 0).then(|| KeyRepeat {
+                            keycode,
+                            next: Instant::now() + state.repeat_delay,
+                        });
+                    },
+                    KeyState::Released => state.release(keycode),
+                    _ => (),
+                }
+            },
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                if let Some(xkb_state) = state.state.as_mut() {
+                    xkb_state.update_mask(mods_depressed, mods_latched, mods_locked, group);
+
+                    let layout = state
+                        .layouts
+                        .lock()
+                        .expect("poisoned layouts")
+                        .update(xkb_state);
+
+                    // also sent on entering the surface, with the modifiers held at the time
+                    if state.focused {
+                        let modifiers = get_modifiers(xkb_state);
+                        send_event(state.window(), Event::ModifiersChanged(modifiers));
+
+                        if let Some(layout) = layout {
+                            send_event(state.window(), Event::LayoutChanged(layout));
+                        }
+                    }
+                }
+            },
+            wl_keyboard::Event::RepeatInfo { rate, delay } => {
+                state.repeat_rate = rate;
+                state.repeat_delay = Duration::from_millis(delay as u64);
+
+                if rate <= 0 {
+                    state.repeat = None;
+                }
+            },
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_manager_v3::{
+    self, ZwpTextInputManagerV3,
+};
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
+    self, ContentHint, ContentPurpose, ZwpTextInputV3,
+};
+
+use super::{send_event, KeyboardState};
+use crate::Event;
+
+// the state of the input method, which is changed in batches ended by `done`
+#[derive(Debug, Default)]
+pub(super) struct Composition {
+    composing: bool,
+
+    // text, cursor begin and cursor end, as sent by the compositor
+    preedit: Option<(Option<String>, i32, i32)>,
+    commit: Option<String>,
+}
+
+impl Composition {
+    // the events for the changes since the last `done`
+    fn done(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        if let Some(text) = self.commit.take() {
+            // input methods may commit without composing first, e.g. a full width space
+            if !std::mem::take(&mut self.composing) {
+                events.push(Event::CompositionStart);
+            }
+
+            events.push(Event::CompositionEnd(Some(text)));
+        }
+
+        match self.preedit.take() {
+            Some((Some(text), begin, end)) if !text.is_empty() => {
+                if !std::mem::replace(&mut self.composing, true) {
+                    events.push(Event::CompositionStart);
+                }
+
+                // both are -1 when the cursor should be hidden
+                let cursor = usize::try_from(begin)
+                    .ok()
+                    .zip(usize::try_from(end).ok())
+                    .map(|(begin, end)| begin.min(end)..end.max(begin));
+
+                events.push(Event::CompositionUpdate { text, cursor });
+            },
+            // an empty preedit without a commit cancels the composition
+            _ if std::mem::take(&mut self.composing) => events.push(Event::CompositionEnd(None)),
+            _ => (),
+        }
+
+        events
+    }
+
+    fn cancel(&mut self) -> Option<Event> {
+        self.preedit = None;
+        self.commit = None;
+
+        std::mem::take(&mut self.composing).then_some(Event::CompositionEnd(None))
+    }
+}
+
+impl Dispatch<ZwpTextInputManagerV3, ()> for KeyboardState {
+    fn event(
+        _: &mut Self,
+        _: &ZwpTextInputManagerV3,
+        _: zwp_text_input_manager_v3::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpTextInputV3, ()> for KeyboardState {
+    fn event(
+        state: &mut Self,
+        text_input: &ZwpTextInputV3,
+        event: zwp_text_input_v3::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            // the input method is only used once enabled, for as long as the surface has focus.
+            // the cursor rectangle isn't set since where the text cursor is isn't known here
+            zwp_text_input_v3::Event::Enter { surface }
+                if surface.id().as_ptr() as usize == state.surface =>
+            {
+                text_input.enable();
+                text_input.set_content_type(ContentHint::None, ContentPurpose::Normal);
+                text_input.commit();
+            },
+            zwp_text_input_v3::Event::Leave { surface }
+                if surface.id().as_ptr() as usize == state.surface =>
+            {
+                text_input.disable();
+                text_input.commit();
+
+                if let Some(event) = state.composition.cancel() {
+                    send_event(state.window(), event);
+                }
+            },
+            zwp_text_input_v3::Event::PreeditString {
+                text,
+                cursor_begin,
+                cursor_end,
+            } => {
+                state.composition.preedit = Some((text, cursor_begin, cursor_end));
+            },
+            zwp_text_input_v3::Event::CommitString { text } => {
+                state.composition.commit = text;
+            },
+            zwp_text_input_v3::Event::Done { .. } => {
+                for event in state.composition.done() {
+                    send_event(state.window(), event);
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn done(
+        composition: &mut Composition,
+        preedit: Option<(&str, i32, i32)>,
+        commit: Option<&str>,
+    ) -> Vec<Event> {
+        composition.preedit =
+            preedit.map(|(text, begin, end)| (Some(text.to_string()), begin, end));
+        composition.commit = commit.map(str::to_string);
+
+        composition.done()
+    }
+
+    fn update(text: &str, cursor: Option<std::ops::Range<usize>>) -> Event {
+        Event::CompositionUpdate {
+            text: text.to_string(),
+            cursor,
+        }
+    }
+
+    #[test]
+    fn preedit_update() {
+        let mut composition = Composition::default();
+
+        assert_eq!(
+            done(&mut composition, Some(("か", 3, 3)), None),
+            vec![Event::CompositionStart, update("か", Some(3..3))]
+        );
+        // the cursor may be given backwards, and is hidden with -1
+        assert_eq!(
+            done(&mut composition, Some(("かん", 6, 0)), None),
+            vec![update("かん", Some(0..6))]
+        );
+        assert_eq!(
+            done(&mut composition, Some(("漢", -1, -1)), None),
+            vec![update("漢", None)]
+        );
+    }
+
+    #[test]
+    fn commit() {
+        let mut composition = Composition::default();
+
+        done(&mut composition, Some(("かん", 6, 6)), None);
+        // the preedit is cleared along with the commit
+        assert_eq!(
+            done(&mut composition, None, Some("漢")),
+            vec![Event::CompositionEnd(Some("漢".to_string()))]
+        );
+
+        // committed without composing first
+        assert_eq!(
+            done(&mut composition, None, Some("\u{3000}")),
+            vec![
+                Event::CompositionStart,
+                Event::CompositionEnd(Some("\u{3000}".to_string())),
+            ]
+        );
+
+        // the next composition starts in the same batch as the commit
+        assert_eq!(
+            done(&mut composition, Some(("じ", 3, 3)), Some("字")),
+            vec![
+                Event::CompositionStart,
+                Event::CompositionEnd(Some("字".to_string())),
+                Event::CompositionStart,
+                update("じ", Some(3..3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn preedit_cleared() {
+        let mut composition = Composition::default();
+
+        done(&mut composition, Some(("か", 3, 3)), None);
+        assert_eq!(
+            done(&mut composition, Some(("", 0, 0)), None),
+            vec![Event::CompositionEnd(None)]
+        );
+        assert_eq!(done(&mut composition, None, None), vec![]);
+
+        // losing focus while composing
+        done(&mut composition, Some(("か", 3, 3)), None);
+        assert_eq!(composition.cancel(), Some(Event::CompositionEnd(None)));
+        assert_eq!(composition.cancel(), None);
+    }
+}
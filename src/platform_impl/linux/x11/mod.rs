@@ -0,0 +1,655 @@
+#[cfg(target_os = "linux")]
+mod xtest;
+
+use std::ffi::{c_int, c_void, CString};
+use std::fmt::Display;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use raw_window_handle::RawDisplayHandle;
+use rustix::net::SocketAddrAny;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{
+    AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt as _, CreateWindowAux,
+    EventMask, KeyButMask, Keycode, NotifyDetail, Window, WindowClass,
+};
+use x11rb::protocol::Event as X11Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::xcb_ffi::XCBConnection;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT};
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::xtest::XTestSender;
+use super::layout::Layouts;
+use super::translate_key::{get_modifiers, press_text, translate_key};
+use super::xkb::{ComposeState, Context, Keymap, RuleNames, State};
+use super::{send_event, AttachError, PlatformWindowHandle};
+use crate::repeat::RepeatTracker;
+use crate::{code_location, Event, KeyEvent, KeyboardState, LayoutInfo, Modifiers};
+
+fn x11_error(e: impl Display) -> AttachError {
+    AttachError::X11(e.to_string())
+}
+
+#[derive(Debug)]
+pub(crate) struct X11Listener {
+    conn: Arc<RustConnection>,
+    display: X11Display,
+    root: Window,
+    window: Window,
+    // window owned by our own connection, a message is sent to it to wake up the event thread
+    wake_window: Window,
+    // shared with the event thread so the server can be asked which keys are down
+    keyboard: Arc<Mutex<Option<Keyboard>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl X11Listener {
+    pub(crate) fn connect(window: Window, display: X11Display) -> Result<Self, AttachError> {
+        let (conn, screen_num) = x11rb::connect(display.name.as_deref()).map_err(x11_error)?;
+        let root = conn
+            .setup()
+            .roots
+            .get(display.screen.unwrap_or(screen_num))
+            .ok_or_else(|| x11_error("the display has no such screen"))?
+            .root;
+
+        let wake_window = conn.generate_id().map_err(x11_error)?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            wake_window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .map_err(x11_error)?
+        .check()
+        .map_err(x11_error)?;
+
+        Ok(Self {
+            conn: Arc::new(conn),
+            display,
+            root,
+            window,
+            wake_window,
+            keyboard: Arc::new(Mutex::new(None)),
+            thread: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn attatch(&self) -> Result<(), AttachError> {
+        // by default the server reports auto-repeat as release/press pairs,
+        // with detectable auto-repeat only the presses are sent
+        if self
+            .conn
+            .extension_information(xkb::X11_EXTENSION_NAME)
+            .map_err(x11_error)?
+            .is_some()
+        {
+            let xkb_ext = self
+                .conn
+                .xkb_use_extension(1, 0)
+                .map_err(x11_error)?
+                .reply()
+                .map_err(x11_error)?;
+
+            if xkb_ext.supported {
+                self.conn
+                    .xkb_per_client_flags(
+                        xkb::ID::USE_CORE_KBD.into(),
+                        xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT,
+                        xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT,
+                        xkb::BoolCtrl::from(0u32),
+                        xkb::BoolCtrl::from(0u32),
+                        xkb::BoolCtrl::from(0u32),
+                    )
+                    .map_err(x11_error)?
+                    .reply()
+                    .map_err(x11_error)?;
+
+                // state changes are sent regardless of focus, e.g. caps lock turned on elsewhere
+                self.conn
+                    .xkb_select_events(
+                        xkb::ID::USE_CORE_KBD.into(),
+                        xkb::EventType::from(0u16),
+                        xkb::EventType::STATE_NOTIFY,
+                        xkb::MapPart::from(0u16),
+                        xkb::MapPart::from(0u16),
+                        &xkb::SelectEventsAux::new(),
+                    )
+                    .map_err(x11_error)?
+                    .check()
+                    .map_err(x11_error)?;
+            }
+        }
+
+        // this only changes the event mask of our connection, the owner of the window is unaffected
+        self.conn
+            .change_window_attributes(
+                self.window,
+                &ChangeWindowAttributesAux::new().event_mask(
+                    EventMask::KEY_PRESS | EventMask::KEY_RELEASE | EventMask::FOCUS_CHANGE,
+                ),
+            )
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)?;
+
+        *self.keyboard.lock().map_err(|_| AttachError::PoisonError)? =
+            Some(Keyboard::new(&self.conn, self.root, self.display.clone())?);
+
+        let (conn, keyboard) = (self.conn.clone(), self.keyboard.clone());
+        let (window, wake_window) = (self.window, self.wake_window);
+        let thread = thread::spawn(move || event_loop(conn, window, wake_window, keyboard));
+
+        *self.thread.lock().map_err(|_| AttachError::PoisonError)? = Some(thread);
+
+        Ok(())
+    }
+}
+
+impl X11Listener {
+    // `None` if the listener isn't attached or the server couldn't be reached
+    pub(crate) fn query_state(&self) -> Option<KeyboardState> {
+        let keys = self.conn.query_keymap().ok()?.reply().ok()?.keys;
+        // the pointer reply has the same modifier and group mask as key events
+        let mask = self.conn.query_pointer(self.root).ok()?.reply().ok()?.mask;
+
+        let mut keyboard = self.keyboard.lock().ok()?;
+
+        Some(keyboard.as_mut()?.query(&keys, mask))
+    }
+
+    pub(crate) fn layouts(&self) -> Vec<LayoutInfo> {
+        let keyboard = self.keyboard.lock().expect("poisoned keyboard");
+
+        keyboard
+            .as_ref()
+            .map(|k| k.layouts.installed())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn active_layout(&self) -> Option<LayoutInfo> {
+        let keyboard = self.keyboard.lock().expect("poisoned keyboard");
+
+        keyboard.as_ref()?.layouts.active()
+    }
+
+    pub(crate) fn detach(&self) -> Result<(), AttachError> {
+        let Some(thread) = self
+            .thread
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?
+            .take()
+        else {
+            return Ok(());
+        };
+
+        // errors are ignored as the connection may already be closed, in which case
+        // the event thread will have exited by itself
+        let _ = self.conn.change_window_attributes(
+            self.window,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+        );
+
+        // with an empty event mask the event is delivered to the creator of the window, which is us
+        let _ = self.conn.send_event(
+            false,
+            self.wake_window,
+            EventMask::NO_EVENT,
+            ClientMessageEvent::new(32, self.wake_window, AtomEnum::NONE, [0u32; 5]),
+        );
+        let _ = self.conn.flush();
+
+        let _ = thread.join();
+
+        Ok(())
+    }
+}
+
+impl Drop for X11Listener {
+    fn drop(&mut self) {
+        let _ = self.detach();
+    }
+}
+
+fn event_loop(
+    conn: Arc<RustConnection>,
+    window: Window,
+    wake_window: Window,
+    keyboard: Arc<Mutex<Option<Keyboard>>>,
+) {
+    let mut repeats: RepeatTracker<Keycode> = RepeatTracker::new();
+    // unknown until the first focus event
+    let mut focused = None;
+
+    while let Ok(event) = conn.wait_for_event() {
+        let mut keyboard = keyboard.lock().expect("poisoned keyboard");
+        let Some(keyboard) = keyboard.as_mut() else {
+            break;
+        };
+
+        match event {
+            X11Event::KeyPress(e) if e.event == window => {
+                send_event(
+                    PlatformWindowHandle::X11(window),
+                    Event::Press {
+                        key: keyboard.press(e.detail, e.state),
+                        repeat_count: repeats.press(e.detail),
+                    },
+                );
+            },
+            X11Event::KeyRelease(e) if e.event == window => {
+                repeats.release(&e.detail);
+
+                send_event(
+                    PlatformWindowHandle::X11(window),
+                    Event::Release(keyboard.key_event(e.detail, e.state)),
+                );
+            },
+            // focus moving between the window and its children doesn't matter, nor do the events
+            // sent to the window under the pointer while another window has focus
+            X11Event::FocusIn(e) if e.event == window && is_focus_change(e.detail) => {
+                set_focus(window, &mut focused, true);
+            },
+            X11Event::FocusOut(e) if e.event == window && is_focus_change(e.detail) => {
+                // the releases of keys that are still down go to the window focused instead
+                repeats.clear();
+                keyboard.reset_compose();
+                set_focus(window, &mut focused, false);
+            },
+            X11Event::XkbStateNotify(e) => {
+                send_event(
+                    PlatformWindowHandle::X11(window),
+                    Event::ModifiersChanged(keyboard.state_notify(&e)),
+                );
+
+                // the group is the active layout
+                if let Some(layout) = keyboard.layouts.update(&keyboard.state) {
+                    send_event(
+                        PlatformWindowHandle::X11(window),
+                        Event::LayoutChanged(layout),
+                    );
+                }
+            },
+            // sent to every client when the keyboard mapping changes, e.g. by `setxkbmap`
+            X11Event::MappingNotify(_) => {
+                if let Some(layout) = keyboard.reload(&conn) {
+                    send_event(
+                        PlatformWindowHandle::X11(window),
+                        Event::LayoutChanged(layout),
+                    );
+                }
+            },
+            X11Event::ClientMessage(e) if e.window == wake_window => break,
+            _ => (),
+        }
+    }
+}
+
+fn is_focus_change(detail: NotifyDetail) -> bool {
+    detail != NotifyDetail::INFERIOR && detail != NotifyDetail::POINTER
+}
+
+// the server can send several focus events in a row, e.g. when a grab ends
+fn set_focus(window: Window, focused: &mut Option<bool>, focus: bool) {
+    if focused.replace(focus) != Some(focus) {
+        send_event(
+            PlatformWindowHandle::X11(window),
+            if focus {
+                Event::FocusGained
+            } else {
+                Event::FocusLost
+            },
+        );
+    }
+}
+
+#[derive(Debug)]
+struct Keyboard {
+    context: Context,
+    state: State,
+    // `None` if the locale has no compose table
+    compose: Option<ComposeState>,
+    layouts: Layouts,
+    root: Window,
+    // the server's keymap is fetched with it, `None` if libxcb couldn't be loaded
+    xcb: Option<XCBConnection>,
+    // the core state of key events only has the effective modifiers, these are as of the last
+    // XKB state notify
+    latched_mods: u32,
+    locked_mods: u32,
+}
+
+impl Keyboard {
+    fn new(conn: &RustConnection, root: Window, display: X11Display) -> Result<Self, AttachError> {
+        let context = Context::new()
+            .ok_or_else(|| AttachError::X11("failed to load libxkbcommon".to_string()))?;
+
+        let names = rule_names(conn, root).map_err(x11_error)?;
+        let xcb = xcb_connection(&display);
+        let state = keyboard_state(&context, xcb.as_ref(), &names)
+            .ok_or_else(|| AttachError::X11("failed to compile keymap".to_string()))?;
+
+        let mut keyboard = Self {
+            compose: ComposeState::from_locale(&context),
+            context,
+            state,
+            layouts: Layouts::default(),
+            root,
+            xcb,
+            latched_mods: 0,
+            locked_mods: 0,
+        };
+
+        keyboard
+            .layouts
+            .set_keymap(keyboard.state.keymap(), Some(&names));
+        keyboard.sync_state(conn);
+        keyboard.layouts.update(&keyboard.state);
+
+        Ok(keyboard)
+    }
+
+    // returns the active layout if the new keymap changed it
+    fn reload(&mut self, conn: &RustConnection) -> Option<LayoutInfo> {
+        let names = rule_names(conn, self.root).ok()?;
+        let state = keyboard_state(&self.context, self.xcb.as_ref(), &names)?;
+
+        self.layouts.set_keymap(state.keymap(), Some(&names));
+        self.state = state;
+        self.sync_state(conn);
+
+        self.layouts.update(&self.state)
+    }
+
+    // a new state has nothing held and the first layout active, until the server is asked
+    fn sync_state(&mut self, conn: &RustConnection) {
+        let Some(reply) = conn
+            .xkb_get_state(xkb::ID::USE_CORE_KBD.into())
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        else {
+            return;
+        };
+
+        self.latched_mods = u16::from(reply.latched_mods).into();
+        self.locked_mods = u16::from(reply.locked_mods).into();
+
+        self.state.update_mask(
+            u32::from(u16::from(reply.base_mods)),
+            self.latched_mods,
+            self.locked_mods,
+            u8::from(reply.group).into(),
+        );
+    }
+
+    fn key_event(&mut self, keycode: Keycode, state: KeyButMask) -> KeyEvent {
+        self.update_mask(state);
+
+        let (key, code, raw_key_event_data) = translate_key(&self.state, keycode.into());
+
+        KeyEvent {
+            key,
+            text: None,
+            composed: None,
+            code,
+            location: code_location(code),
+            modifiers: get_modifiers(&self.state),
+            timestamp: SystemTime::now(),
+            synthetic: false,
+            raw: raw_key_event_data,
+        }
+    }
+
+    // like `key_event`, with the text typed by the key
+    fn press(&mut self, keycode: Keycode, state: KeyButMask) -> KeyEvent {
+        let mut key = self.key_event(keycode, state);
+        press_text(&self.state, self.compose.as_mut(), &mut key);
+
+        key
+    }
+
+    fn reset_compose(&mut self) {
+        if let Some(compose) = &mut self.compose {
+            compose.reset();
+        }
+    }
+
+    // the modifiers after an XKB state change
+    fn state_notify(&mut self, e: &xkb::StateNotifyEvent) -> Modifiers {
+        self.latched_mods = u16::from(e.latched_mods).into();
+        self.locked_mods = u16::from(e.locked_mods).into();
+
+        self.state.update_mask(
+            u32::from(u16::from(e.base_mods)),
+            self.latched_mods,
+            self.locked_mods,
+            u8::from(e.group).into(),
+        );
+
+        get_modifiers(&self.state)
+    }
+
+    // `keys` is a bit for every keycode, as returned by `XQueryKeymap`
+    fn query(&mut self, keys: &[u8; 32], state: KeyButMask) -> KeyboardState {
+        let keys = (0..=u8::MAX)
+            .filter(|keycode| keys[*keycode as usize / 8] & (1 << (keycode % 8)) != 0)
+            .map(|keycode| self.key_event(keycode, state))
+            .collect::<Vec<_>>();
+
+        self.update_mask(state);
+
+        KeyboardState::new(keys, get_modifiers(&self.state))
+    }
+
+    fn update_mask(&mut self, state: KeyButMask) {
+        // the core state has the effective modifiers in the low byte and the group in bits 13
+        // and 14. the ones that aren't latched or locked are held down
+        let mask = u16::from(state);
+        let depressed = u32::from(mask & 0xff) & !(self.latched_mods | self.locked_mods);
+
+        self.state.update_mask(
+            depressed,
+            self.latched_mods,
+            self.locked_mods,
+            u32::from((mask >> 13) & 0b11),
+        );
+    }
+}
+
+// libxkbcommon-x11 needs an xcb connection, `None` if libxcb couldn't be loaded
+pub(crate) fn xcb_connection(display: &X11Display) -> Option<XCBConnection> {
+    let name = display.name.as_deref().map(CString::new).transpose().ok()?;
+
+    XCBConnection::connect(name.as_deref())
+        .ok()
+        .map(|(conn, _)| conn)
+}
+
+// the keymap the server uses, which includes changes made with e.g. `xmodmap` or `xkbcomp`
+pub(crate) fn server_state(context: &Context, conn: &XCBConnection) -> Option<State> {
+    State::from_x11_device(context, conn.get_raw_xcb_connection())
+}
+
+// if the server's keymap can't be fetched it is compiled from `names` instead
+fn keyboard_state(
+    context: &Context,
+    conn: Option<&XCBConnection>,
+    names: &RuleNames,
+) -> Option<State> {
+    conn.and_then(|conn| server_state(context, conn))
+        .or_else(|| Keymap::from_names(context, names).and_then(State::new))
+}
+
+// the X server and screen a window is on
+#[derive(Clone, Debug, Default)]
+pub(crate) struct X11Display {
+    // `None` for the one in `DISPLAY`
+    name: Option<String>,
+    screen: Option<usize>,
+}
+
+impl X11Display {
+    // the display of the connection the window was made with, which may not be the one in
+    // `DISPLAY`. a handle without a connection means the default display
+    pub(crate) fn from_handle(handle: RawDisplayHandle) -> Result<Self, AttachError> {
+        let (fd, screen) = match handle {
+            RawDisplayHandle::Xlib(h) => (h.display.map(xlib_fd).transpose()?, h.screen),
+            RawDisplayHandle::Xcb(h) => (h.connection.map(xcb_fd).transpose()?, h.screen),
+            _ => return Err(x11_error("not an X11 display handle")),
+        };
+
+        let name = match fd {
+            Some(fd) => {
+                // SAFETY: the connection of the handle is open, so its socket is too
+                let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+                let address = rustix::net::getpeername(fd)
+                    .map_err(x11_error)?
+                    .ok_or_else(|| x11_error("the display's socket isn't connected"))?;
+
+                Some(
+                    display_name(&address)
+                        .ok_or_else(|| x11_error("unsupported address of the X server"))?,
+                )
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            name,
+            screen: usize::try_from(screen).ok(),
+        })
+    }
+}
+
+// the socket of an Xlib `Display`, libX11 is already loaded by whoever opened it
+fn xlib_fd(display: NonNull<c_void>) -> Result<RawFd, AttachError> {
+    // SAFETY: the signature matches `XConnectionNumber`, and `display` is a valid `Display`
+    unsafe {
+        let lib = libloading::Library::new("libX11.so.6").map_err(x11_error)?;
+        let connection_number = lib
+            .get::<unsafe extern "C" fn(*mut c_void) -> c_int>(b"XConnectionNumber\0")
+            .map_err(x11_error)?;
+
+        Ok(connection_number(display.as_ptr()))
+    }
+}
+
+fn xcb_fd(conn: NonNull<c_void>) -> Result<RawFd, AttachError> {
+    // SAFETY: the connection outlives the wrapper, which doesn't disconnect it when dropped
+    let conn = unsafe { XCBConnection::from_raw_xcb_connection(conn.as_ptr(), false) }
+        .map_err(x11_error)?;
+
+    Ok(conn.as_raw_fd())
+}
+
+// the display name that connects to the same server as `address`, the other end of a client's
+// socket
+fn display_name(address: &SocketAddrAny) -> Option<String> {
+    // e.g. `/tmp/.X11-unix/X1` for `:1`
+    let local = |path: &[u8]| {
+        let display: u16 = std::str::from_utf8(path.strip_prefix(b"/tmp/.X11-unix/X")?)
+            .ok()?
+            .parse()
+            .ok()?;
+
+        Some(format!(":{display}"))
+    };
+
+    match address {
+        SocketAddrAny::Unix(unix) => {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            if let Some(name) = unix.abstract_name() {
+                return local(name);
+            }
+
+            // any other socket can be connected to by its path
+            let path = unix.path()?;
+            local(path.to_bytes()).or_else(|| path.to_str().ok().map(str::to_string))
+        },
+        SocketAddrAny::V4(address) => Some(format!(
+            "{}:{}",
+            address.ip(),
+            address.port().checked_sub(6000)?
+        )),
+        SocketAddrAny::V6(address) => Some(format!(
+            "{}:{}",
+            address.ip(),
+            address.port().checked_sub(6000)?
+        )),
+        _ => None,
+    }
+}
+
+// the names the server keymap was compiled from, as set by `setxkbmap`
+fn rule_names(conn: &impl Connection, root: Window) -> Result<RuleNames, ReplyError> {
+    let atom = conn.intern_atom(true, b"_XKB_RULES_NAMES")?.reply()?.atom;
+
+    if atom == x11rb::NONE {
+        return Ok(RuleNames::default());
+    }
+
+    let reply = conn
+        .get_property(false, root, atom, AtomEnum::STRING, 0, 1024)?
+        .reply()?;
+
+    let mut names = reply
+        .value
+        .split(|b| *b == 0)
+        .map(|name| String::from_utf8_lossy(name).into_owned());
+
+    Ok(RuleNames {
+        rules: names.next().unwrap_or_default(),
+        model: names.next().unwrap_or_default(),
+        layout: names.next().unwrap_or_default(),
+        variant: names.next().unwrap_or_default(),
+        options: names.next().unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use rustix::net::SocketAddrUnix;
+
+    use super::*;
+
+    #[test]
+    fn display_names() {
+        let unix = |path| SocketAddrAny::Unix(SocketAddrUnix::new(path).unwrap());
+
+        assert_eq!(
+            display_name(&unix("/tmp/.X11-unix/X1")).as_deref(),
+            Some(":1")
+        );
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            display_name(&SocketAddrAny::Unix(
+                SocketAddrUnix::new_abstract_name(b"/tmp/.X11-unix/X0").unwrap()
+            ))
+            .as_deref(),
+            Some(":0")
+        );
+        assert_eq!(
+            display_name(&unix("/run/user/1000/x11")).as_deref(),
+            Some("/run/user/1000/x11")
+        );
+
+        let tcp = |port| SocketAddrAny::V4(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), port));
+        assert_eq!(display_name(&tcp(6010)).as_deref(), Some("10.0.0.2:10"));
+        assert_eq!(display_name(&tcp(22)), None);
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use std::sync::Mutex;
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{Window, KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+use x11rb::protocol::xtest::{self, ConnectionExt as _};
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::xcb_ffi::XCBConnection;
+
+use super::rule_names;
+use crate::platform_impl::platform::xkb::{Context, Keymap, RuleNames, State};
+use crate::platform_impl::SendError;
+
+fn xtest_error(e: impl Display) -> SendError {
+    SendError::X11(e.to_string())
+}
+
+#[derive(Debug)]
+pub(crate) struct XTestSender {
+    // libxkbcommon-x11 needs an xcb connection to fetch the server's keymap
+    conn: XCBConnection,
+    root: Window,
+    context: Context,
+    // when the server's keymap can't be fetched, recompiled whenever the rule names on the root
+    // window change
+    keymap: Mutex<Option<(RuleNames, Keymap)>>,
+}
+
+impl XTestSender {
+    // there is no window to take the display from, so it is the one in `DISPLAY`
+    pub(crate) fn connect() -> Result<Self, SendError> {
+        let (conn, screen_num) = XCBConnection::connect(None).map_err(xtest_error)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        if conn
+            .extension_information(xtest::X11_EXTENSION_NAME)
+            .map_err(xtest_error)?
+            .is_none()
+        {
+            return Err(xtest_error("the XTEST extension is not available"));
+        }
+
+        // needed to ask for the active group
+        let xkb = conn
+            .xkb_use_extension(1, 0)
+            .map_err(xtest_error)?
+            .reply()
+            .map_err(xtest_error)?;
+
+        if !xkb.supported {
+            return Err(xtest_error("the XKEYBOARD extension is not available"));
+        }
+
+        let context = Context::new().ok_or_else(|| xtest_error("failed to load libxkbcommon"))?;
+
+        Ok(Self {
+            conn,
+            root,
+            context,
+            keymap: Mutex::new(None),
+        })
+    }
+
+    // the keymap of the server and the active layout, the same one the X11 listener decodes
+    // keys with, so changes made with e.g. `xmodmap` are seen
+    pub(crate) fn keymap(&self) -> Result<(Keymap, u32), SendError> {
+        if let Some(state) =
+            State::from_x11_device(&self.context, self.conn.get_raw_xcb_connection())
+        {
+            return Ok((state.keymap().clone(), state.layout()));
+        }
+
+        let names = rule_names(&self.conn, self.root).map_err(xtest_error)?;
+        let mut cached = self.keymap.lock().map_err(|_| SendError::PoisonError)?;
+
+        let keymap = match &*cached {
+            Some((cached_names, keymap)) if *cached_names == names => keymap.clone(),
+            _ => {
+                let keymap = Keymap::from_names(&self.context, &names)
+                    .ok_or_else(|| xtest_error("failed to compile keymap"))?;
+                *cached = Some((names, keymap.clone()));
+
+                keymap
+            },
+        };
+
+        let group = self
+            .conn
+            .xkb_get_state(xkb::ID::USE_CORE_KBD.into())
+            .map_err(xtest_error)?
+            .reply()
+            .map_err(xtest_error)?
+            .group;
+
+        Ok((keymap, u8::from(group) as u32))
+    }
+
+    pub(crate) fn key(&self, keycode: u32, pressed: bool) -> Result<(), SendError> {
+        let kind = if pressed {
+            KEY_PRESS_EVENT
+        } else {
+            KEY_RELEASE_EVENT
+        };
+
+        self.conn
+            .xtest_fake_input(kind, keycode as u8, x11rb::CURRENT_TIME, self.root, 0, 0, 0)
+            .map_err(xtest_error)?;
+
+        Ok(())
+    }
+
+    // waits for the server to process the events so errors aren't lost
+    pub(crate) fn flush(&self) -> Result<(), SendError> {
+        self.conn.sync().map_err(xtest_error)
+    }
+}
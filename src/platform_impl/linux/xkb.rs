@@ -0,0 +1,603 @@
+// thin wrappers around the parts of libxkbcommon used by the backends,
+// the library is loaded at runtime so it isn't a build dependency
+use std::env;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::ptr::{self, NonNull};
+
+use xkbcommon_dl::x11::{
+    xkb_x11_setup_xkb_extension_flags, xkbcommon_x11_option, XKB_X11_MIN_MAJOR_XKB_VERSION,
+    XKB_X11_MIN_MINOR_XKB_VERSION,
+};
+use xkbcommon_dl::{
+    xkb_compose_compile_flags, xkb_compose_feed_result, xkb_compose_state, xkb_compose_state_flags,
+    xkb_compose_status, xkb_context, xkb_context_flags, xkb_key_direction, xkb_keymap,
+    xkb_keymap_compile_flags, xkb_keymap_format, xkb_rule_names, xkb_state, xkb_state_component,
+    xkbcommon_compose_handle, xkbcommon_compose_option, xkbcommon_handle, xkbcommon_option,
+};
+use xkeysym::Keysym;
+
+extern "C" {
+    // strings returned by libxkbcommon are allocated with malloc
+    fn free(ptr: *mut c_void);
+}
+
+// rules, model, layout, variant and options used to compile a keymap,
+// empty fields are filled in with the system defaults
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct RuleNames {
+    pub(crate) rules: String,
+    pub(crate) model: String,
+    pub(crate) layout: String,
+    pub(crate) variant: String,
+    pub(crate) options: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct Context(NonNull<xkb_context>);
+
+// SAFETY: xkbcommon objects may be moved between threads as long as they aren't used concurrently
+unsafe impl Send for Context {}
+
+impl Context {
+    // `None` if libxkbcommon could not be loaded
+    pub(crate) fn new() -> Option<Self> {
+        let xkb = xkbcommon_option()?;
+
+        let context = unsafe { (xkb.xkb_context_new)(xkb_context_flags::XKB_CONTEXT_NO_FLAGS) };
+
+        NonNull::new(context).map(Self)
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { (xkbcommon_handle().xkb_context_unref)(self.0.as_ptr()) }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Keymap(NonNull<xkb_keymap>);
+
+unsafe impl Send for Keymap {}
+
+impl Keymap {
+    pub(crate) fn from_names(context: &Context, names: &RuleNames) -> Option<Self> {
+        let to_c = |s: &str| CString::new(s).ok().filter(|s| !s.is_empty());
+
+        let rules = to_c(&names.rules);
+        let model = to_c(&names.model);
+        let layout = to_c(&names.layout);
+        let variant = to_c(&names.variant);
+        let options = to_c(&names.options);
+
+        let as_ptr = |s: &Option<CString>| s.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+
+        let names = xkb_rule_names {
+            rules: as_ptr(&rules),
+            model: as_ptr(&model),
+            layout: as_ptr(&layout),
+            variant: as_ptr(&variant),
+            options: as_ptr(&options),
+        };
+
+        let keymap = unsafe {
+            (xkbcommon_handle().xkb_keymap_new_from_names)(
+                context.0.as_ptr(),
+                &names,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            )
+        };
+
+        NonNull::new(keymap).map(Self)
+    }
+
+    pub(crate) fn from_buffer(context: &Context, buffer: &[u8]) -> Option<Self> {
+        // the buffer may or may not include the trailing nul
+        let buffer = buffer.strip_suffix(&[0]).unwrap_or(buffer);
+
+        let keymap = unsafe {
+            (xkbcommon_handle().xkb_keymap_new_from_buffer)(
+                context.0.as_ptr(),
+                buffer.as_ptr() as *const c_char,
+                buffer.len(),
+                xkb_keymap_format::XKB_KEYMAP_FORMAT_TEXT_V1,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            )
+        };
+
+        NonNull::new(keymap).map(Self)
+    }
+
+    pub(crate) fn min_keycode(&self) -> u32 {
+        unsafe { (xkbcommon_handle().xkb_keymap_min_keycode)(self.0.as_ptr()) }
+    }
+
+    pub(crate) fn max_keycode(&self) -> u32 {
+        unsafe { (xkbcommon_handle().xkb_keymap_max_keycode)(self.0.as_ptr()) }
+    }
+
+    // `name` must be nul terminated, `None` if the keymap doesn't have the modifier
+    pub(crate) fn mod_get_index(&self, name: &[u8]) -> Option<u32> {
+        let index = unsafe {
+            (xkbcommon_handle().xkb_keymap_mod_get_index)(
+                self.0.as_ptr(),
+                name.as_ptr() as *const c_char,
+            )
+        };
+
+        (index != u32::MAX).then_some(index)
+    }
+
+    pub(crate) fn num_layouts(&self) -> u32 {
+        unsafe { (xkbcommon_handle().xkb_keymap_num_layouts)(self.0.as_ptr()) }
+    }
+
+    // the name of every layout, e.g. `English (US)`, empty for layouts without one.
+    // there is no `xkb_keymap_layout_get_name` in the bindings, so they are read from the keymap
+    pub(crate) fn layout_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.num_layouts() as usize];
+
+        let keymap = unsafe {
+            (xkbcommon_handle().xkb_keymap_get_as_string)(
+                self.0.as_ptr(),
+                xkb_keymap_format::XKB_KEYMAP_FORMAT_TEXT_V1,
+            )
+        };
+
+        if keymap.is_null() {
+            return names;
+        }
+
+        let text = unsafe { CStr::from_ptr(keymap) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { free(keymap as *mut c_void) };
+
+        // e.g. `name[Group1]="English (US)";` in the symbols section
+        for line in text.lines() {
+            let Some((group, name)) = line
+                .trim()
+                .strip_prefix("name[")
+                .and_then(|line| line.split_once(']'))
+            else {
+                continue;
+            };
+
+            let index = group
+                .get(5..)
+                .filter(|_| group[..5].eq_ignore_ascii_case("group"))
+                .and_then(|n| n.parse::<usize>().ok());
+            let name = name
+                .split_once('"')
+                .and_then(|(_, name)| name.rsplit_once('"'))
+                .map(|(name, _)| name);
+
+            if let (Some(index), Some(name)) = (index, name) {
+                if let Some(slot) = index.checked_sub(1).and_then(|i| names.get_mut(i)) {
+                    *slot = name.to_string();
+                }
+            }
+        }
+
+        names
+    }
+
+    pub(crate) fn key_repeats(&self, keycode: u32) -> bool {
+        unsafe { (xkbcommon_handle().xkb_keymap_key_repeats)(self.0.as_ptr(), keycode) == 1 }
+    }
+
+    // the first keysym of the given shift level, `NO_SYMBOL` if there are none
+    pub(crate) fn key_get_sym_by_level(&self, keycode: u32, layout: u32, level: u32) -> Keysym {
+        let mut syms = ptr::null();
+
+        let len = unsafe {
+            (xkbcommon_handle().xkb_keymap_key_get_syms_by_level)(
+                self.0.as_ptr(),
+                keycode,
+                layout,
+                level,
+                &mut syms,
+            )
+        };
+
+        if len <= 0 || syms.is_null() {
+            return xkeysym::NO_SYMBOL;
+        }
+
+        Keysym::new(unsafe { *syms })
+    }
+}
+
+impl Clone for Keymap {
+    fn clone(&self) -> Self {
+        Self(unsafe {
+            NonNull::new_unchecked((xkbcommon_handle().xkb_keymap_ref)(self.0.as_ptr()))
+        })
+    }
+}
+
+impl Drop for Keymap {
+    fn drop(&mut self) {
+        unsafe { (xkbcommon_handle().xkb_keymap_unref)(self.0.as_ptr()) }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct State {
+    state: NonNull<xkb_state>,
+    keymap: Keymap,
+    // the modifiers set by AltGr, see `level3_mask`
+    level3: u32,
+    // see `scroll_lock_mask`
+    scroll_lock: u32,
+}
+
+unsafe impl Send for State {}
+
+impl State {
+    pub(crate) fn new(keymap: Keymap) -> Option<Self> {
+        let state = unsafe { (xkbcommon_handle().xkb_state_new)(keymap.0.as_ptr()) };
+
+        NonNull::new(state).map(|state| Self::from_raw(state, keymap))
+    }
+
+    fn from_raw(state: NonNull<xkb_state>, keymap: Keymap) -> Self {
+        Self {
+            state,
+            level3: level3_mask(&keymap),
+            scroll_lock: scroll_lock_mask(&keymap),
+            keymap,
+        }
+    }
+
+    // the keymap and state of the X server's core keyboard, `conn` is an xcb connection.
+    // `None` if libxkbcommon-x11 couldn't be loaded or the server doesn't support XKB
+    pub(crate) fn from_x11_device(context: &Context, conn: *mut c_void) -> Option<Self> {
+        let xkb = xkbcommon_x11_option()?;
+
+        let supported = unsafe {
+            (xkb.xkb_x11_setup_xkb_extension)(
+                conn,
+                XKB_X11_MIN_MAJOR_XKB_VERSION,
+                XKB_X11_MIN_MINOR_XKB_VERSION,
+                xkb_x11_setup_xkb_extension_flags::XKB_X11_SETUP_XKB_EXTENSION_NO_FLAGS,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+
+        if supported != 1 {
+            return None;
+        }
+
+        let device = unsafe { (xkb.xkb_x11_get_core_keyboard_device_id)(conn) };
+
+        if device == -1 {
+            return None;
+        }
+
+        let keymap = NonNull::new(unsafe {
+            (xkb.xkb_x11_keymap_new_from_device)(
+                context.0.as_ptr(),
+                conn,
+                device,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            )
+        })
+        .map(Keymap)?;
+
+        let state = unsafe { (xkb.xkb_x11_state_new_from_device)(keymap.0.as_ptr(), conn, device) };
+
+        NonNull::new(state).map(|state| Self::from_raw(state, keymap))
+    }
+
+    pub(crate) fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    pub(crate) fn update_mask(&mut self, depressed: u32, latched: u32, locked: u32, group: u32) {
+        unsafe {
+            (xkbcommon_handle().xkb_state_update_mask)(
+                self.state.as_ptr(),
+                depressed,
+                latched,
+                locked,
+                0,
+                0,
+                group,
+            )
+        };
+    }
+
+    // used when there is no server tracking the modifiers for us
+    #[cfg(all(feature = "global", target_os = "linux"))]
+    pub(crate) fn update_key(&mut self, keycode: u32, pressed: bool) {
+        let direction = if pressed {
+            xkb_key_direction::XKB_KEY_DOWN
+        } else {
+            xkb_key_direction::XKB_KEY_UP
+        };
+
+        unsafe {
+            (xkbcommon_handle().xkb_state_update_key)(self.state.as_ptr(), keycode, direction)
+        };
+    }
+
+    pub(crate) fn key_get_one_sym(&self, keycode: u32) -> Keysym {
+        Keysym::new(unsafe {
+            (xkbcommon_handle().xkb_state_key_get_one_sym)(self.state.as_ptr(), keycode)
+        })
+    }
+
+    // the text typed by the key with the modifiers of the state, empty if there is none
+    pub(crate) fn key_get_utf8(&self, keycode: u32) -> String {
+        let xkb = xkbcommon_handle();
+
+        // the length doesn't include the nul, which is written anyway
+        let len = unsafe {
+            (xkb.xkb_state_key_get_utf8)(self.state.as_ptr(), keycode, ptr::null_mut(), 0)
+        };
+        let mut buffer = vec![0u8; usize::try_from(len).unwrap_or(0) + 1];
+
+        unsafe {
+            (xkb.xkb_state_key_get_utf8)(
+                self.state.as_ptr(),
+                keycode,
+                buffer.as_mut_ptr() as *mut c_char,
+                buffer.len(),
+            )
+        };
+        buffer.pop();
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+
+    // the index of the active layout
+    pub(crate) fn layout(&self) -> u32 {
+        unsafe {
+            (xkbcommon_handle().xkb_state_serialize_layout)(
+                self.state.as_ptr(),
+                xkb_state_component::XKB_STATE_LAYOUT_EFFECTIVE,
+            )
+        }
+    }
+
+    pub(crate) fn key_get_layout(&self, keycode: u32) -> u32 {
+        unsafe { (xkbcommon_handle().xkb_state_key_get_layout)(self.state.as_ptr(), keycode) }
+    }
+
+    // the modifiers set by AltGr, 0 if the keymap has no AltGr
+    pub(crate) fn level3_mask(&self) -> u32 {
+        self.level3
+    }
+
+    pub(crate) fn level3_is_active(&self) -> bool {
+        let mods = unsafe {
+            (xkbcommon_handle().xkb_state_serialize_mods)(
+                self.state.as_ptr(),
+                xkb_state_component::XKB_STATE_MODS_EFFECTIVE,
+            )
+        };
+
+        mods & self.level3 != 0
+    }
+
+    // false if the keymap doesn't give Scroll Lock a modifier
+    pub(crate) fn scroll_lock_is_active(&self) -> bool {
+        let mods = unsafe {
+            (xkbcommon_handle().xkb_state_serialize_mods)(
+                self.state.as_ptr(),
+                xkb_state_component::XKB_STATE_MODS_EFFECTIVE,
+            )
+        };
+
+        mods & self.scroll_lock != 0
+    }
+
+    pub(crate) fn mod_name_is_active(&self, name: &[u8]) -> bool {
+        unsafe {
+            (xkbcommon_handle().xkb_state_mod_name_is_active)(
+                self.state.as_ptr(),
+                name.as_ptr() as *const c_char,
+                xkb_state_component::XKB_STATE_MODS_EFFECTIVE,
+            ) == 1
+        }
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        unsafe { (xkbcommon_handle().xkb_state_unref)(self.state.as_ptr()) }
+    }
+}
+
+// the modifiers set by pressing the key that types `ISO_Level3_Shift` (AltGr). that is `Mod5` on
+// the default keymaps, but the keymap can assign it any modifier. 0 if no key types it
+fn level3_mask(keymap: &Keymap) -> u32 {
+    key_mask(keymap, Keysym::ISO_Level3_Shift)
+}
+
+// the modifier locked by the key that types `Scroll_Lock`. the default keymaps don't assign it
+// one, so it is usually 0
+fn scroll_lock_mask(keymap: &Keymap) -> u32 {
+    key_mask(keymap, Keysym::Scroll_Lock)
+}
+
+// the modifiers set or locked by pressing the first key that types `keysym`, 0 if no key does
+fn key_mask(keymap: &Keymap, keysym: Keysym) -> u32 {
+    let xkb = xkbcommon_handle();
+
+    let Some((keycode, layout)) =
+        (keymap.min_keycode()..=keymap.max_keycode()).find_map(|keycode| {
+            (0..keymap.num_layouts())
+                .find(|layout| keymap.key_get_sym_by_level(keycode, *layout, 0) == keysym)
+                .map(|layout| (keycode, layout))
+        })
+    else {
+        return 0;
+    };
+
+    let Some(state) = NonNull::new(unsafe { (xkb.xkb_state_new)(keymap.0.as_ptr()) }) else {
+        return 0;
+    };
+
+    let mask = unsafe {
+        (xkb.xkb_state_update_mask)(state.as_ptr(), 0, 0, 0, 0, 0, layout);
+        (xkb.xkb_state_update_key)(state.as_ptr(), keycode, xkb_key_direction::XKB_KEY_DOWN);
+
+        (xkb.xkb_state_serialize_mods)(
+            state.as_ptr(),
+            xkb_state_component::XKB_STATE_MODS_EFFECTIVE,
+        )
+    };
+
+    unsafe { (xkb.xkb_state_unref)(state.as_ptr()) };
+
+    mask
+}
+
+// the result of feeding a keysym to `ComposeState`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Compose {
+    // the key isn't part of a sequence
+    Ignored,
+    // the key started or continued a sequence
+    Composing,
+    Composed(String),
+    // the key isn't part of the sequence it was pressed in, which types the characters of the
+    // sequence's dead keys before its own, the same as on Windows
+    Cancelled(String),
+}
+
+// the dead key and compose sequences of the user's locale, e.g. `dead_acute` then `e` is `é`
+#[derive(Debug)]
+pub(crate) struct ComposeState {
+    state: NonNull<xkb_compose_state>,
+    // the characters of the dead keys in the sequence so far
+    dead_keys: String,
+}
+
+unsafe impl Send for ComposeState {}
+
+impl ComposeState {
+    // `None` if the compose module couldn't be loaded or the locale has no compose table
+    pub(crate) fn from_locale(context: &Context) -> Option<Self> {
+        // the same order as setlocale
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|locale| !locale.is_empty())
+            .unwrap_or_else(|| "C".to_string());
+
+        Self::new(context, &CString::new(locale).ok()?)
+    }
+
+    pub(crate) fn new(context: &Context, locale: &CStr) -> Option<Self> {
+        let xkb = xkbcommon_compose_option()?;
+
+        let table = unsafe {
+            (xkb.xkb_compose_table_new_from_locale)(
+                context.0.as_ptr(),
+                locale.as_ptr(),
+                xkb_compose_compile_flags::XKB_COMPOSE_COMPILE_NO_FLAGS,
+            )
+        };
+
+        if table.is_null() {
+            return None;
+        }
+
+        // the state keeps its own reference to the table
+        let state = unsafe {
+            let state = (xkb.xkb_compose_state_new)(
+                table,
+                xkb_compose_state_flags::XKB_COMPOSE_STATE_NO_FLAGS,
+            );
+            (xkb.xkb_compose_table_unref)(table);
+            state
+        };
+
+        NonNull::new(state).map(|state| Self {
+            state,
+            dead_keys: String::new(),
+        })
+    }
+
+    // feeds the keysym of a key press
+    pub(crate) fn feed(&mut self, keysym: Keysym) -> Compose {
+        let xkb = xkbcommon_compose_handle();
+        let state = self.state.as_ptr();
+
+        // modifier keys are ignored so they can be held for the next key of the sequence
+        if unsafe { (xkb.xkb_compose_state_feed)(state, keysym.raw()) }
+            == xkb_compose_feed_result::XKB_COMPOSE_FEED_IGNORED
+        {
+            return Compose::Ignored;
+        }
+
+        match unsafe { (xkb.xkb_compose_state_get_status)(state) } {
+            xkb_compose_status::XKB_COMPOSE_NOTHING => return Compose::Ignored,
+            xkb_compose_status::XKB_COMPOSE_COMPOSED => self.dead_keys.clear(),
+            xkb_compose_status::XKB_COMPOSE_CANCELLED => {
+                return Compose::Cancelled(std::mem::take(&mut self.dead_keys))
+            },
+            _ => {
+                self.dead_keys.extend(dead_key_char(keysym));
+                return Compose::Composing;
+            },
+        }
+
+        // the length doesn't include the nul, which is written anyway
+        let len = unsafe { (xkb.xkb_compose_state_get_utf8)(state, ptr::null_mut(), 0) };
+        let mut buffer = vec![0u8; usize::try_from(len).unwrap_or(0) + 1];
+
+        unsafe {
+            (xkb.xkb_compose_state_get_utf8)(
+                state,
+                buffer.as_mut_ptr() as *mut c_char,
+                buffer.len(),
+            )
+        };
+        buffer.pop();
+
+        Compose::Composed(String::from_utf8(buffer).unwrap_or_default())
+    }
+
+    // forgets the keys of an unfinished sequence
+    pub(crate) fn reset(&mut self) {
+        self.dead_keys.clear();
+        unsafe { (xkbcommon_compose_handle().xkb_compose_state_reset)(self.state.as_ptr()) }
+    }
+}
+
+impl Drop for ComposeState {
+    fn drop(&mut self) {
+        unsafe { (xkbcommon_compose_handle().xkb_compose_state_unref)(self.state.as_ptr()) }
+    }
+}
+
+// the character a dead key types on its own, which keysyms don't have
+fn dead_key_char(keysym: Keysym) -> Option<char> {
+    let c = match keysym {
+        Keysym::dead_grave => '`',
+        Keysym::dead_acute => '´',
+        Keysym::dead_circumflex => '^',
+        Keysym::dead_tilde => '~',
+        Keysym::dead_macron => '¯',
+        Keysym::dead_breve => '˘',
+        Keysym::dead_abovedot => '˙',
+        Keysym::dead_diaeresis => '¨',
+        Keysym::dead_abovering => '˚',
+        Keysym::dead_doubleacute => '˝',
+        Keysym::dead_caron => 'ˇ',
+        Keysym::dead_cedilla => '¸',
+        Keysym::dead_ogonek => '˛',
+        Keysym::dead_iota => 'ͺ',
+        _ => return None,
+    };
+
+    Some(c)
+}
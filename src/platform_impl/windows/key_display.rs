@@ -1,8 +1,8 @@
 use std::fmt::{self, Display};
 
-use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyboardLayout, ToUnicodeEx};
+use windows::Win32::UI::Input::KeyboardAndMouse::GetKeyboardLayout;
 
-use super::translate_key::ToKeyboardState;
+use super::translate_key::{key_text, KeyText, ToKeyboardState};
 use crate::{Key, KeyEvent};
 
 impl Display for KeyEvent {
@@ -10,30 +10,25 @@ impl Display for KeyEvent {
         match &self.key {
             // this displays using the given the modifiers of the keypress
             // which is unlike the character contained in Key::Character as that is created with the `NO_MODIFIERS` state
-            Key::Character(_) => {
-                let kb_layout = unsafe { GetKeyboardLayout(0) };
+            Key::Character(_) | Key::Dead => {
+                // the text of a press is from when it happened, rather than the current layout
+                if let Some(text) = &self.text {
+                    return write!(f, "{}", text);
+                }
 
-                // `WCHAR` is UTF-16, so 2 bytes
-                let key_char: &mut [u16] = &mut [0];
+                let kb_layout = unsafe { GetKeyboardLayout(0) };
 
-                let result = unsafe {
-                    ToUnicodeEx(
-                        self.raw.virtual_key_code,
-                        self.raw.virtual_scan_code,
-                        &self.modifiers.to_keyboard_state(),
-                        key_char,
-                        1,
-                        kb_layout,
-                    )
-                };
+                let text = key_text(
+                    self.raw.virtual_key_code,
+                    self.raw.virtual_scan_code,
+                    &self.modifiers.to_keyboard_state(),
+                    kb_layout,
+                );
 
-                if result == 0 {
-                    return write!(f, "");
+                match text {
+                    Some(KeyText::Dead(text) | KeyText::Text(text)) => write!(f, "{}", text),
+                    None => write!(f, ""),
                 }
-
-                let str_char = String::from_utf16(key_char).unwrap_or_default();
-
-                write!(f, "{}", str_char)
             },
             key => {
                 write!(f, "{}", key)
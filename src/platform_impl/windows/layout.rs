@@ -0,0 +1,62 @@
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Globalization::{
+    GetLocaleInfoEx, LCIDToLocaleName, LOCALE_SLOCALIZEDDISPLAYNAME,
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyboardLayout, GetKeyboardLayoutList};
+use windows::Win32::UI::TextServices::HKL;
+use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
+
+use crate::{LayoutId, LayoutInfo};
+
+// `LOCALE_NAME_MAX_LENGTH`
+const LOCALE_NAME_LEN: usize = 85;
+
+pub(crate) fn layouts() -> Vec<LayoutInfo> {
+    let len = unsafe { GetKeyboardLayoutList(None) };
+    let mut hkls = vec![HKL::default(); len.max(0) as usize];
+
+    // a layout may have been removed in between
+    let len = unsafe { GetKeyboardLayoutList(Some(&mut hkls)) };
+    hkls.truncate(len.max(0) as usize);
+
+    hkls.into_iter().map(layout_info).collect()
+}
+
+// the layout of the thread that owns the window, which its key messages are translated with
+pub(crate) fn active_layout(hwnd: HWND) -> LayoutInfo {
+    let thread = unsafe { GetWindowThreadProcessId(hwnd, None) };
+
+    layout_info(unsafe { GetKeyboardLayout(thread) })
+}
+
+pub(crate) fn layout_info(hkl: HKL) -> LayoutInfo {
+    // the low word is the language, the high word the layout itself
+    let language_id = (hkl.0 as usize & 0xffff) as u32;
+
+    let mut tag = [0u16; LOCALE_NAME_LEN];
+    let len = unsafe { LCIDToLocaleName(language_id, Some(&mut tag), 0) };
+
+    // lengths include the nul
+    let language = (len > 1).then(|| String::from_utf16_lossy(&tag[..len as usize - 1]));
+
+    let name = language.as_ref().and_then(|_| {
+        let mut name = [0u16; 256];
+        let len = unsafe {
+            GetLocaleInfoEx(
+                PCWSTR(tag.as_ptr()),
+                LOCALE_SLOCALIZEDDISPLAYNAME,
+                Some(&mut name),
+            )
+        };
+
+        (len > 1).then(|| String::from_utf16_lossy(&name[..len as usize - 1]))
+    });
+
+    LayoutInfo {
+        // e.g. `German (Germany)`
+        name: name.or_else(|| language.clone()).unwrap_or_default(),
+        language,
+        id: LayoutId::Hkl(hkl.0),
+    }
+}
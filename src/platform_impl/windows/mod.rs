@@ -1,21 +1,37 @@
 mod key_display;
+mod layout;
+mod scancode;
 mod translate_key;
 mod window;
 
 #[cfg(feature = "global")]
 mod global;
 
+use std::collections::HashMap;
 use std::fmt::{self, Display};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::SystemTime;
 
 use raw_window_handle::Win32WindowHandle;
-use windows::Win32::Foundation::{HWND, WPARAM};
-use windows::Win32::UI::WindowsAndMessaging::{WM_KEYDOWN, WM_SYSKEYDOWN};
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyboardState, VIRTUAL_KEY, VK_CONTROL, VK_LBUTTON, VK_MBUTTON, VK_MENU, VK_RBUTTON,
+    VK_SHIFT, VK_SPACE, VK_XBUTTON1, VK_XBUTTON2,
+};
+use windows::Win32::UI::TextServices::HKL;
+use windows::Win32::UI::WindowsAndMessaging::{
+    PeekMessageW, MSG, PM_NOREMOVE, WM_CHAR, WM_DEADCHAR, WM_KEYDOWN, WM_SYSKEYDOWN,
+};
 
-use self::translate_key::get_modifiers;
+use self::layout::layout_info;
+use self::scancode::{lparam_to_scancode, scancode_to_code};
+#[cfg(feature = "hotkeys")]
+use self::translate_key::level_char;
+use self::translate_key::{get_modifiers, press_text, PRESSED};
+pub(crate) use self::window::window_handle;
 use crate::platform_impl::platform::translate_key::translate_key;
-use crate::{Event, KeyEvent, SendSyncRwh, CHANNELS};
+use crate::repeat::RepeatTracker;
+use crate::{code_location, send_event, Event, KeyEvent, KeyboardState, Modifiers, SmolStr};
 
 pub(crate) type PlatformWindowHandle = isize;
 
@@ -23,6 +39,7 @@ pub(crate) type PlatformWindowHandle = isize;
 #[derive(Clone, Debug)]
 pub enum AttachError {
     AttachError(u32),
+    DetachError(u32),
     PoisonError,
 }
 
@@ -30,42 +47,222 @@ impl Display for AttachError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AttachError::AttachError(e) => write!(f, "failed to attach listener: ({e:#01X})"),
+            AttachError::DetachError(e) => write!(f, "failed to detach listener: ({e:#01X})"),
             AttachError::PoisonError => write!(f, "failed to attach listener: poisoned RwLock"),
         }
     }
 }
 
-static REPEAT_COUNT: AtomicUsize = AtomicUsize::new(0);
+lazy_static::lazy_static! {
+    // key: HWND, value: the keys held down in that window, by scan code
+    pub(crate) static ref REPEATS: Mutex<HashMap<isize, RepeatTracker<u32>>> = Mutex::new(HashMap::new());
+    // key: HWND, value: the character of a dead key typed that is waiting for the key it is
+    // combined with
+    pub(crate) static ref DEAD_KEYS: Mutex<HashMap<isize, u16>> = Mutex::new(HashMap::new());
+    // key: HWND, value: the modifiers last sent to that window's listeners
+    pub(crate) static ref MODIFIERS: Mutex<HashMap<isize, Modifiers>> = Mutex::new(HashMap::new());
+}
 
-pub(crate) fn handle_key_message(msg: u32, hwnd: HWND, wparam: WPARAM) {
+pub(crate) fn handle_key_message(msg: u32, hwnd: HWND, wparam: WPARAM, lparam: LPARAM) {
     let modifiers = get_modifiers();
     let (key, raw_key_event_data) = translate_key(wparam);
 
+    let scancode = lparam_to_scancode(lparam);
+    let code = scancode_to_code(scancode);
+
+    // the scan code of the message rather than of the virtual key, which keys sent with
+    // `SendInput` may not have
+    let raw_key_event_data = RawKeyEventData {
+        scancode,
+        #[cfg(feature = "hotkeys")]
+        level_char: level_char(&raw_key_event_data, modifiers),
+        ..raw_key_event_data
+    };
+
+    let pressed = matches!(msg, WM_KEYDOWN | WM_SYSKEYDOWN);
+
+    let (text, composed) = match pressed.then(|| compose(hwnd, &raw_key_event_data)) {
+        None => (None, None),
+        Some(Compose::Ignored) => (press_text(&raw_key_event_data), None),
+        Some(Compose::Composing) => (None, None),
+        Some(Compose::Composed(composed)) => (Some(composed.clone()), Some(composed)),
+        Some(Compose::Cancelled(dead_key)) => {
+            let text = press_text(&raw_key_event_data).unwrap_or_default();
+            (Some(SmolStr::from(format!("{dead_key}{text}"))), None)
+        },
+    };
+
     let key_event = KeyEvent {
         key,
+        text,
+        composed,
+        code,
+        location: code_location(code),
         modifiers,
         timestamp: SystemTime::now(),
+        synthetic: false,
         raw: raw_key_event_data,
     };
 
-    let event = if matches!(msg, WM_KEYDOWN | WM_SYSKEYDOWN) {
-        let e = Event::Press {
-            key: key_event,
-            repeat_count: REPEAT_COUNT.load(Ordering::Relaxed),
-        };
+    let event = {
+        let mut repeats = REPEATS.lock().expect("poisoned repeats");
+        let repeats = repeats.entry(hwnd.0).or_insert_with(RepeatTracker::new);
 
-        REPEAT_COUNT.fetch_add(1, Ordering::Relaxed);
+        if pressed {
+            Event::Press {
+                key: key_event,
+                repeat_count: repeats.press(scancode),
+            }
+        } else {
+            repeats.release(&scancode);
+            Event::Release(key_event)
+        }
+    };
 
-        e
-    } else {
-        REPEAT_COUNT.store(0, Ordering::Relaxed);
-        Event::Release(key_event)
+    send_event(hwnd.0, event);
+
+    // the key state has this message applied already, which is what tells if a lock key turned
+    // its lock on or off
+    let changed = MODIFIERS
+        .lock()
+        .expect("poisoned modifiers")
+        .insert(hwnd.0, modifiers)
+        != Some(modifiers);
+
+    if changed {
+        send_event(hwnd.0, Event::ModifiersChanged(modifiers));
+    }
+}
+
+// what a key press does to a dead key typed before it
+enum Compose {
+    // no dead key was typed
+    Ignored,
+    // the key is a dead key, or a modifier held for the key after the dead key
+    Composing,
+    Composed(SmolStr),
+    // the key can't be combined with the dead key, whose character is typed before its own
+    Cancelled(SmolStr),
+}
+
+// `TranslateMessage` has already posted the character of the key by the time its key down message
+// is handled, a `WM_DEADCHAR` for a dead key and a `WM_CHAR` for the key that finishes it
+//
+// the dead keys aren't locked while peeking, which can dispatch sent messages such as the
+// `WM_KILLFOCUS` that removes the window's dead key
+fn compose(hwnd: HWND, raw: &RawKeyEventData) -> Compose {
+    let dead_keys = || DEAD_KEYS.lock().expect("poisoned dead keys");
+    let mut msg = MSG::default();
+
+    let peek = |msg: &mut MSG, filter: u32| unsafe {
+        PeekMessageW(msg, hwnd, filter, filter, PM_NOREMOVE).as_bool()
+    };
+
+    if peek(&mut msg, WM_DEADCHAR) {
+        dead_keys().insert(hwnd.0, msg.wParam.0 as u16);
+        return Compose::Composing;
+    }
+
+    let Some(dead_key) = dead_keys().get(&hwnd.0).copied() else {
+        return Compose::Ignored;
     };
 
-    let channels = CHANNELS.read().expect("poisoned channels");
-    let channel = &channels[&SendSyncRwh(hwnd.0)];
+    // modifier keys don't type anything, so the dead key keeps waiting
+    if !peek(&mut msg, WM_CHAR) {
+        return Compose::Composing;
+    }
+
+    // dropped while peeking if the window lost focus
+    if dead_keys().remove(&hwnd.0).is_none() {
+        return Compose::Ignored;
+    }
 
-    let _ = channel.0.send(event);
+    let text = |c: u16| {
+        String::from_utf16(&[c])
+            .map(SmolStr::from)
+            .unwrap_or_default()
+    };
+    let typed = msg.wParam.0 as u16;
+
+    // the first of the two characters posted for a key that can't be combined is the dead key's
+    // own, which space types on its own instead
+    if typed == dead_key && raw.virtual_key_code != u32::from(VK_SPACE.0) {
+        Compose::Cancelled(text(dead_key))
+    } else {
+        Compose::Composed(text(typed))
+    }
+}
+
+pub(crate) fn handle_focus_message(hwnd: HWND, focused: bool) {
+    if !focused {
+        // the releases of keys that are still down go to the window focused instead
+        if let Some(repeats) = REPEATS.lock().expect("poisoned repeats").get_mut(&hwnd.0) {
+            repeats.clear();
+        }
+
+        // the key finishing a dead key pressed before losing focus goes to another window
+        DEAD_KEYS
+            .lock()
+            .expect("poisoned dead keys")
+            .remove(&hwnd.0);
+    }
+
+    send_event(
+        hwnd.0,
+        if focused {
+            Event::FocusGained
+        } else {
+            Event::FocusLost
+        },
+    );
+}
+
+// the keys held down as of the last keyboard message the calling thread handled
+pub(crate) fn held_keys() -> Option<KeyboardState> {
+    let mut keyboard_state = [0u8; 256];
+    unsafe { GetKeyboardState(&mut keyboard_state) }.ok()?;
+
+    let modifiers = get_modifiers();
+
+    let keys = (0..=u8::MAX)
+        .filter(|vk| keyboard_state[*vk as usize] & PRESSED != 0)
+        // the mouse buttons, and the keys that are either their left or right key
+        .filter(|vk| {
+            !matches!(
+                VIRTUAL_KEY(u16::from(*vk)),
+                VK_LBUTTON
+                    | VK_RBUTTON
+                    | VK_MBUTTON
+                    | VK_XBUTTON1
+                    | VK_XBUTTON2
+                    | VK_SHIFT
+                    | VK_CONTROL
+                    | VK_MENU
+            )
+        })
+        .map(|vk| {
+            let (key, raw_key_event_data) = translate_key(WPARAM(vk.into()));
+            let code = scancode_to_code(raw_key_event_data.scancode);
+
+            KeyEvent {
+                key,
+                text: None,
+                composed: None,
+                code,
+                location: code_location(code),
+                modifiers,
+                timestamp: SystemTime::now(),
+                synthetic: false,
+                raw: raw_key_event_data,
+            }
+        });
+
+    Some(KeyboardState::new(keys, modifiers))
+}
+
+// `lparam` is the new layout
+pub(crate) fn handle_layout_message(hwnd: HWND, lparam: LPARAM) {
+    send_event(hwnd.0, Event::LayoutChanged(layout_info(HKL(lparam.0))));
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash)]
@@ -73,15 +270,37 @@ pub(crate) fn handle_key_message(msg: u32, hwnd: HWND, wparam: WPARAM) {
 pub(crate) struct RawKeyEventData {
     virtual_key_code: u32,
     virtual_scan_code: u32,
+    // with the extended key prefix, which tells apart e.g. the two enter keys
+    scancode: u32,
+    // the character of the level shift and AltGr chose, e.g. `+` for shift+`=` on a US layout,
+    // looked up when the key is pressed since the layout is per thread
+    #[cfg(feature = "hotkeys")]
+    level_char: Option<char>,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct KeyboardListener {
-    handle: Win32WindowHandle,
+impl RawKeyEventData {
+    pub(crate) fn scancode(&self) -> u32 {
+        self.scancode
+    }
+
+    #[cfg(test)]
+    pub(crate) fn test(scancode: u32) -> Self {
+        Self {
+            virtual_key_code: 0,
+            virtual_scan_code: scancode & 0xff,
+            scancode,
+            #[cfg(feature = "hotkeys")]
+            level_char: None,
+        }
+    }
+
+    #[cfg(feature = "hotkeys")]
+    pub(crate) fn level_char(&self) -> Option<char> {
+        self.level_char
+    }
 }
 
-#[cfg(feature = "hotkeys")]
-#[derive(Clone, Debug)]
-pub(crate) struct HotkeyListener {
+#[derive(Debug)]
+pub(crate) struct KeyboardListener {
     handle: Win32WindowHandle,
 }
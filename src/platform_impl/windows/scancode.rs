@@ -0,0 +1,244 @@
+use windows::Win32::Foundation::LPARAM;
+
+use crate::Code;
+
+// the scan code from the `lParam` of a key message, extended keys are prefixed with `0xE0`
+pub(crate) fn lparam_to_scancode(lparam: LPARAM) -> u32 {
+    let scancode = (lparam.0 >> 16) as u32 & 0xFF;
+    let extended = (lparam.0 >> 24) & 1 == 1;
+
+    if extended {
+        0xE000 | scancode
+    } else {
+        scancode
+    }
+}
+
+// see: https://www.win.tue.nl/~aeb/linux/kbd/scancodes-1.html
+// and: https://www.w3.org/TR/uievents-code/
+pub(crate) fn scancode_to_code(scancode: u32) -> Code {
+    match scancode {
+        // writing system keys
+        0x0029 => Code::Backquote,
+        0x002B => Code::Backslash,
+        0x001A => Code::BracketLeft,
+        0x001B => Code::BracketRight,
+        0x0033 => Code::Comma,
+        0x000B => Code::Digit0,
+        0x0002 => Code::Digit1,
+        0x0003 => Code::Digit2,
+        0x0004 => Code::Digit3,
+        0x0005 => Code::Digit4,
+        0x0006 => Code::Digit5,
+        0x0007 => Code::Digit6,
+        0x0008 => Code::Digit7,
+        0x0009 => Code::Digit8,
+        0x000A => Code::Digit9,
+        0x000D => Code::Equal,
+        0x0056 => Code::IntlBackslash,
+        0x0073 => Code::IntlRo,
+        0x007D => Code::IntlYen,
+        0x001E => Code::KeyA,
+        0x0030 => Code::KeyB,
+        0x002E => Code::KeyC,
+        0x0020 => Code::KeyD,
+        0x0012 => Code::KeyE,
+        0x0021 => Code::KeyF,
+        0x0022 => Code::KeyG,
+        0x0023 => Code::KeyH,
+        0x0017 => Code::KeyI,
+        0x0024 => Code::KeyJ,
+        0x0025 => Code::KeyK,
+        0x0026 => Code::KeyL,
+        0x0032 => Code::KeyM,
+        0x0031 => Code::KeyN,
+        0x0018 => Code::KeyO,
+        0x0019 => Code::KeyP,
+        0x0010 => Code::KeyQ,
+        0x0013 => Code::KeyR,
+        0x001F => Code::KeyS,
+        0x0014 => Code::KeyT,
+        0x0016 => Code::KeyU,
+        0x002F => Code::KeyV,
+        0x0011 => Code::KeyW,
+        0x002D => Code::KeyX,
+        0x0015 => Code::KeyY,
+        0x002C => Code::KeyZ,
+        0x000C => Code::Minus,
+        0x0034 => Code::Period,
+        0x0028 => Code::Quote,
+        0x0027 => Code::Semicolon,
+        0x0035 => Code::Slash,
+
+        // functional keys
+        0x0038 => Code::AltLeft,
+        0xE038 => Code::AltRight,
+        0x000E => Code::Backspace,
+        0x003A => Code::CapsLock,
+        0xE05D => Code::ContextMenu,
+        0x001D => Code::ControlLeft,
+        0xE01D => Code::ControlRight,
+        0x001C => Code::Enter,
+        0xE05B => Code::MetaLeft,
+        0xE05C => Code::MetaRight,
+        0x002A => Code::ShiftLeft,
+        0x0036 => Code::ShiftRight,
+        0x0039 => Code::Space,
+        0x000F => Code::Tab,
+        0x0079 => Code::Convert,
+        0x0070 => Code::KanaMode,
+        // the first is for non-korean layouts, the second for korean ones
+        0x0072 | 0xE0F2 => Code::Lang1,
+        0x0071 | 0xE0F1 => Code::Lang2,
+        0x007B => Code::NonConvert,
+
+        // control pad
+        0xE053 => Code::Delete,
+        0xE04F => Code::End,
+        0xE047 => Code::Home,
+        0xE052 => Code::Insert,
+        0xE051 => Code::PageDown,
+        0xE049 => Code::PageUp,
+
+        // arrow pad
+        0xE050 => Code::ArrowDown,
+        0xE04B => Code::ArrowLeft,
+        0xE04D => Code::ArrowRight,
+        0xE048 => Code::ArrowUp,
+
+        // numpad
+        0xE045 => Code::NumLock,
+        0x0052 => Code::Numpad0,
+        0x004F => Code::Numpad1,
+        0x0050 => Code::Numpad2,
+        0x0051 => Code::Numpad3,
+        0x004B => Code::Numpad4,
+        0x004C => Code::Numpad5,
+        0x004D => Code::Numpad6,
+        0x0047 => Code::Numpad7,
+        0x0048 => Code::Numpad8,
+        0x0049 => Code::Numpad9,
+        0x004E => Code::NumpadAdd,
+        0x007E => Code::NumpadComma,
+        0x0053 => Code::NumpadDecimal,
+        0xE035 => Code::NumpadDivide,
+        0xE01C => Code::NumpadEnter,
+        0x0059 => Code::NumpadEqual,
+        0x0037 => Code::NumpadMultiply,
+        0x004A => Code::NumpadSubtract,
+
+        // function keys
+        0x0001 => Code::Escape,
+        0x003B => Code::F1,
+        0x003C => Code::F2,
+        0x003D => Code::F3,
+        0x003E => Code::F4,
+        0x003F => Code::F5,
+        0x0040 => Code::F6,
+        0x0041 => Code::F7,
+        0x0042 => Code::F8,
+        0x0043 => Code::F9,
+        0x0044 => Code::F10,
+        0x0057 => Code::F11,
+        0x0058 => Code::F12,
+        0x0064 => Code::F13,
+        0x0065 => Code::F14,
+        0x0066 => Code::F15,
+        0x0067 => Code::F16,
+        0x0068 => Code::F17,
+        0x0069 => Code::F18,
+        0x006A => Code::F19,
+        0x006B => Code::F20,
+        0x006C => Code::F21,
+        0x006D => Code::F22,
+        0x006E => Code::F23,
+        0x0076 => Code::F24,
+        // alt + print screen sends its own scan code
+        0xE037 | 0x0054 => Code::PrintScreen,
+        0x0046 => Code::ScrollLock,
+        // ctrl + pause sends its own scan code
+        0x0045 | 0xE046 => Code::Pause,
+
+        // media keys
+        0xE06A => Code::BrowserBack,
+        0xE066 => Code::BrowserFavorites,
+        0xE069 => Code::BrowserForward,
+        0xE032 => Code::BrowserHome,
+        0xE067 => Code::BrowserRefresh,
+        0xE065 => Code::BrowserSearch,
+        0xE068 => Code::BrowserStop,
+        0xE06B => Code::LaunchApp1,
+        0xE021 => Code::LaunchApp2,
+        0xE06C => Code::LaunchMail,
+        0xE022 => Code::MediaPlayPause,
+        0xE06D => Code::MediaSelect,
+        0xE024 => Code::MediaStop,
+        0xE019 => Code::MediaTrackNext,
+        0xE010 => Code::MediaTrackPrevious,
+        0xE05E => Code::Power,
+        0xE05F => Code::Sleep,
+        0xE063 => Code::WakeUp,
+        0xE02E => Code::AudioVolumeDown,
+        0xE020 => Code::AudioVolumeMute,
+        0xE030 => Code::AudioVolumeUp,
+
+        _ => Code::Unidentified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the scan code is in bits 16-23, the extended flag in bit 24
+    fn lparam(scancode: isize, extended: bool) -> LPARAM {
+        // a repeat count of 1 and the transition state of a release, which are ignored
+        LPARAM(1 | scancode << 16 | (extended as isize) << 24 | 1 << 31)
+    }
+
+    #[test]
+    fn lparam_scancodes() {
+        assert_eq!(lparam_to_scancode(lparam(0x1E, false)), 0x001E);
+        assert_eq!(lparam_to_scancode(lparam(0x1D, true)), 0xE01D);
+        assert_eq!(lparam_to_scancode(lparam(0x1C, true)), 0xE01C);
+    }
+
+    #[test]
+    fn writing_system_keys() {
+        assert_eq!(scancode_to_code(0x001E), Code::KeyA);
+        assert_eq!(scancode_to_code(0x0002), Code::Digit1);
+        assert_eq!(scancode_to_code(0x000B), Code::Digit0);
+        assert_eq!(scancode_to_code(0x0039), Code::Space);
+        assert_eq!(scancode_to_code(0x001C), Code::Enter);
+    }
+
+    // the extended flag tells apart keys that share a scan code
+    #[test]
+    fn extended_keys() {
+        assert_eq!(scancode_to_code(0x001D), Code::ControlLeft);
+        assert_eq!(scancode_to_code(0xE01D), Code::ControlRight);
+        assert_eq!(scancode_to_code(0xE038), Code::AltRight);
+        assert_eq!(scancode_to_code(0xE05B), Code::MetaLeft);
+        assert_eq!(scancode_to_code(0xE047), Code::Home);
+        assert_eq!(scancode_to_code(0xE048), Code::ArrowUp);
+        assert_eq!(scancode_to_code(0xE053), Code::Delete);
+    }
+
+    #[test]
+    fn keypad_keys() {
+        assert_eq!(scancode_to_code(0x0052), Code::Numpad0);
+        assert_eq!(scancode_to_code(0x0053), Code::NumpadDecimal);
+        assert_eq!(scancode_to_code(0x0037), Code::NumpadMultiply);
+        assert_eq!(scancode_to_code(0xE01C), Code::NumpadEnter);
+        assert_eq!(scancode_to_code(0xE035), Code::NumpadDivide);
+        // the same keys without num lock are extended
+        assert_eq!(scancode_to_code(0xE052), Code::Insert);
+    }
+
+    #[test]
+    fn unknown_codes() {
+        assert_eq!(scancode_to_code(0x0000), Code::Unidentified);
+        assert_eq!(scancode_to_code(0xE0FF), Code::Unidentified);
+        assert_eq!(scancode_to_code(u32::MAX), Code::Unidentified);
+    }
+}
@@ -1,26 +1,120 @@
 use windows::Win32::Foundation::WPARAM;
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    GetKeyState, GetKeyboardLayout, MapVirtualKeyExW, ToUnicodeEx, MAPVK_VK_TO_VSC, VIRTUAL_KEY,
-    VK_ACCEPT, VK_ATTN, VK_BROWSER_BACK, VK_BROWSER_FAVORITES, VK_BROWSER_FORWARD, VK_BROWSER_HOME,
-    VK_BROWSER_REFRESH, VK_BROWSER_SEARCH, VK_BROWSER_STOP, VK_CANCEL, VK_CAPITAL, VK_CLEAR,
-    VK_CONTROL, VK_CONVERT, VK_DELETE, VK_END, VK_ESCAPE, VK_EXECUTE, VK_F1, VK_F10, VK_F11,
-    VK_F12, VK_F13, VK_F14, VK_F15, VK_F16, VK_F17, VK_F18, VK_F19, VK_F2, VK_F20, VK_F21, VK_F22,
-    VK_F23, VK_F24, VK_F3, VK_F4, VK_F5, VK_F6, VK_F7, VK_F8, VK_F9, VK_HELP, VK_HOME, VK_INSERT,
-    VK_LAUNCH_MAIL, VK_LCONTROL, VK_LMENU, VK_LSHIFT, VK_LWIN, VK_MEDIA_PLAY_PAUSE, VK_MEDIA_STOP,
-    VK_MENU, VK_NUMLOCK, VK_PAUSE, VK_PLAY, VK_PRINT, VK_RCONTROL, VK_RETURN, VK_RMENU, VK_RSHIFT,
-    VK_RWIN, VK_SCROLL, VK_SELECT, VK_SHIFT, VK_TAB,
+    GetKeyState, GetKeyboardLayout, GetKeyboardState, MapVirtualKeyExW, ToUnicodeEx,
+    MAPVK_VK_TO_VSC, MAPVK_VK_TO_VSC_EX, VIRTUAL_KEY, VK_ACCEPT, VK_ATTN, VK_BROWSER_BACK,
+    VK_BROWSER_FAVORITES, VK_BROWSER_FORWARD, VK_BROWSER_HOME, VK_BROWSER_REFRESH,
+    VK_BROWSER_SEARCH, VK_BROWSER_STOP, VK_CANCEL, VK_CAPITAL, VK_CLEAR, VK_CONTROL, VK_CONVERT,
+    VK_DELETE, VK_END, VK_ESCAPE, VK_EXECUTE, VK_F1, VK_F10, VK_F11, VK_F12, VK_F13, VK_F14,
+    VK_F15, VK_F16, VK_F17, VK_F18, VK_F19, VK_F2, VK_F20, VK_F21, VK_F22, VK_F23, VK_F24, VK_F3,
+    VK_F4, VK_F5, VK_F6, VK_F7, VK_F8, VK_F9, VK_HELP, VK_HOME, VK_INSERT, VK_LAUNCH_MAIL,
+    VK_LCONTROL, VK_LMENU, VK_LSHIFT, VK_LWIN, VK_MEDIA_PLAY_PAUSE, VK_MEDIA_STOP, VK_MENU,
+    VK_NUMLOCK, VK_PAUSE, VK_PLAY, VK_PRINT, VK_RCONTROL, VK_RETURN, VK_RMENU, VK_RSHIFT, VK_RWIN,
+    VK_SCROLL, VK_SELECT, VK_SHIFT, VK_TAB,
 };
+use windows::Win32::UI::TextServices::HKL;
 
 use super::RawKeyEventData;
-use crate::{Key, Modifiers};
+use crate::{Key, Modifiers, SmolStr};
 
 type KeyboardState = [u8; 256];
 
 const NO_MODIFIERS: [u8; 256] = [0; 256];
 
-const PRESSED: u8 = 0b10000000;
+pub(super) const PRESSED: u8 = 0b10000000;
 const TOGGLED: u8 = 0b00000001;
 
+// without it, looking up a dead key would drop the dead key typed in the window,
+// or combine it with the looked up key
+const DONT_CHANGE_STATE: u32 = 1 << 2;
+
+// what `ToUnicodeEx` gives for a key
+pub(crate) enum KeyText {
+    // the character the dead key types on its own
+    Dead(String),
+    Text(String),
+}
+
+pub(crate) fn key_text(
+    virtual_key_code: u32,
+    virtual_scan_code: u32,
+    keyboard_state: &KeyboardState,
+    kb_layout: HKL,
+) -> Option<KeyText> {
+    // `WCHAR` is UTF-16, so a character may be 2 of them, and some layouts type several
+    let mut buffer = [0u16; 8];
+
+    let result = unsafe {
+        ToUnicodeEx(
+            virtual_key_code,
+            virtual_scan_code,
+            keyboard_state,
+            &mut buffer,
+            1 | DONT_CHANGE_STATE,
+            kb_layout,
+        )
+    };
+
+    let text = |len: usize| String::from_utf16(&buffer[..len.min(buffer.len())]).ok();
+
+    match result {
+        0 => None,
+        ..=-1 => {
+            let len = buffer.iter().take_while(|c| **c != 0).count();
+            text(len).map(KeyText::Dead)
+        },
+        len => text(len as usize).map(KeyText::Text),
+    }
+}
+
+// the text typed by a key press, using the keyboard state of the message being handled, which is
+// the same as `TranslateMessage` does
+pub(crate) fn press_text(raw: &RawKeyEventData) -> Option<SmolStr> {
+    let mut keyboard_state: KeyboardState = [0; 256];
+    unsafe { GetKeyboardState(&mut keyboard_state) }.ok()?;
+
+    let kb_layout = unsafe { GetKeyboardLayout(0) };
+
+    match key_text(
+        raw.virtual_key_code,
+        raw.virtual_scan_code,
+        &keyboard_state,
+        kb_layout,
+    ) {
+        // e.g. ctrl+a is `\u{1}`
+        Some(KeyText::Text(text)) if !text.chars().any(char::is_control) => Some(text.into()),
+        _ => None,
+    }
+}
+
+// the character typed with only shift and AltGr (ctrl+alt) of `modifiers` applied, which is what
+// accelerators such as `Ctrl++` are written with
+#[cfg(feature = "hotkeys")]
+pub(crate) fn level_char(raw: &RawKeyEventData, modifiers: Modifiers) -> Option<char> {
+    let mut level = modifiers & Modifiers::SHIFT;
+
+    if modifiers.contains(Modifiers::CONTROL | Modifiers::ALT) {
+        level |= Modifiers::CONTROL | Modifiers::ALT;
+    }
+
+    let kb_layout = unsafe { GetKeyboardLayout(0) };
+
+    let Some(KeyText::Text(text)) = key_text(
+        raw.virtual_key_code,
+        raw.virtual_scan_code,
+        &level.to_keyboard_state(),
+        kb_layout,
+    ) else {
+        return None;
+    };
+
+    let mut chars = text.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_control() => Some(c),
+        _ => None,
+    }
+}
+
 pub(crate) trait ToKeyboardState {
     fn to_keyboard_state(&self) -> KeyboardState;
 }
@@ -125,6 +219,9 @@ pub fn translate_key(raw_key_code: WPARAM) -> (Key, RawKeyEventData) {
                 RawKeyEventData {
                     virtual_key_code: 0,
                     virtual_scan_code: 0,
+                    scancode: 0,
+                    #[cfg(feature = "hotkeys")]
+                    level_char: None,
                 },
             );
         },
@@ -134,18 +231,21 @@ pub fn translate_key(raw_key_code: WPARAM) -> (Key, RawKeyEventData) {
 
     let scan_code = unsafe { MapVirtualKeyExW(key_code, MAPVK_VK_TO_VSC, kb_layout) };
 
-    // `WCHAR` is UTF-16, so 2 bytes
-    let key_char: &mut [u16] = &mut [0];
-
-    // a `0` result will fall through to the non-printable characters,
+    // a `None` result will fall through to the non-printable characters,
     // which it is likely to be anyway (I.E shift, ctrl, etc),
     // and if it wasnt one of those it will return Key::Unidentified
-    // so we dont need to check the result
-    unsafe { ToUnicodeEx(key_code, scan_code, &NO_MODIFIERS, key_char, 1, kb_layout) };
-
-    let char_code = key_char[0];
+    let printable = match key_text(key_code, scan_code, &NO_MODIFIERS, kb_layout) {
+        Some(KeyText::Dead(_)) => Some(Key::Dead),
+        Some(KeyText::Text(text)) if !text.chars().any(char::is_control) => {
+            Some(Key::Character(text))
+        },
+        _ => None,
+    };
 
-    let key = if (0x0000..=0x001F).contains(&char_code) || (0x007F..=0x009F).contains(&char_code) {
+    let key = if let Some(key) = printable {
+        // printable UTF-16 characters
+        key
+    } else {
         // non printable UTF-16 characters
 
         // as far as i can tell these are all the VK keys that have a keyboard-types equivalent
@@ -219,15 +319,6 @@ pub fn translate_key(raw_key_code: WPARAM) -> (Key, RawKeyEventData) {
             VK_F24 => Key::F24,
             _ => Key::Unidentified,
         }
-    } else {
-        // printable UTF-16 characters
-
-        let str_char = String::from_utf16(key_char);
-
-        match str_char {
-            Ok(c) => Key::Character(c),
-            Err(_) => Key::Unidentified,
-        }
     };
 
     (
@@ -235,6 +326,9 @@ pub fn translate_key(raw_key_code: WPARAM) -> (Key, RawKeyEventData) {
         RawKeyEventData {
             virtual_key_code: key_code,
             virtual_scan_code: scan_code,
+            scancode: unsafe { MapVirtualKeyExW(key_code, MAPVK_VK_TO_VSC_EX, kb_layout) },
+            #[cfg(feature = "hotkeys")]
+            level_char: None,
         },
     )
 }
@@ -1,14 +1,31 @@
-use raw_window_handle::RawWindowHandle;
+use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 use windows::Win32::Foundation::{GetLastError, HWND};
 use windows::Win32::UI::WindowsAndMessaging::{SetWindowLongPtrW, GWLP_WNDPROC};
 
 use super::{h_wndproc, WINDOW_SUBCLASSES};
-use crate::platform_impl::{KeyboardListener, PlatformWindowHandle};
-use crate::{AttachError, ListenerError};
+use crate::platform_impl::platform::layout;
+use crate::platform_impl::{
+    held_keys, KeyboardListener, PlatformWindowHandle, DEAD_KEYS, MODIFIERS, REPEATS,
+};
+use crate::{AttachError, KeyboardState, LayoutInfo, ListenerError};
+
+// the window `raw_window_handle` is attached to, without subclassing it
+pub(crate) fn window_handle(
+    raw_window_handle: RawWindowHandle,
+    _raw_display_handle: RawDisplayHandle,
+) -> Result<PlatformWindowHandle, ListenerError> {
+    match raw_window_handle {
+        RawWindowHandle::Win32(h) => Ok(h.hwnd.into()),
+        _ => Err(ListenerError::InvalidHandle),
+    }
+}
 
 impl KeyboardListener {
     pub(crate) fn from_raw_window_handle(
         raw_window_handle: RawWindowHandle,
+        _raw_display_handle: RawDisplayHandle,
+        // there is no input method support on Windows
+        _text_input: bool,
     ) -> Result<Self, ListenerError> {
         match raw_window_handle {
             RawWindowHandle::Win32(h) => Ok(Self { handle: h }),
@@ -35,23 +52,63 @@ impl KeyboardListener {
         Ok(())
     }
 
-    pub(crate) fn platform_window_handle(&self) -> PlatformWindowHandle {
-        self.handle.hwnd.into()
+    pub(crate) fn detach(&self) -> Result<(), AttachError> {
+        let hwnd: isize = self.handle.hwnd.into();
+
+        let mut wndproc = WINDOW_SUBCLASSES
+            .write()
+            .map_err(|_| AttachError::PoisonError)?;
+
+        // already detached
+        let Some(prev) = wndproc.remove(&hwnd) else {
+            return Ok(());
+        };
+
+        let result = unsafe { SetWindowLongPtrW(HWND(hwnd), GWLP_WNDPROC, prev) };
+
+        if result == 0 {
+            wndproc.insert(hwnd, prev);
+
+            return Err(AttachError::DetachError(unsafe { GetLastError().0 }));
+        }
+
+        // the releases of keys that are still down won't reach this window anymore
+        REPEATS
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?
+            .remove(&hwnd);
+
+        // nor will the key finishing a dead key, which would otherwise be combined with the first
+        // key of the next window given the same handle
+        DEAD_KEYS
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?
+            .remove(&hwnd);
+
+        // a listener attached later is sent the modifiers of its first key
+        MODIFIERS
+            .lock()
+            .map_err(|_| AttachError::PoisonError)?
+            .remove(&hwnd);
+
+        Ok(())
+    }
+
+    pub(crate) fn query_state(&self) -> Option<KeyboardState> {
+        held_keys()
+    }
+
+    pub(crate) fn layouts(&self) -> Vec<LayoutInfo> {
+        layout::layouts()
+    }
+
+    pub(crate) fn active_layout(&self) -> Option<LayoutInfo> {
+        Some(layout::active_layout(HWND(self.handle.hwnd.into())))
     }
 }
 
 impl Drop for KeyboardListener {
     fn drop(&mut self) {
-        if let Ok(wndproc) = WINDOW_SUBCLASSES.read() {
-            let hwnd: isize = self.handle.hwnd.into();
-
-            let result = unsafe { SetWindowLongPtrW(HWND(hwnd), GWLP_WNDPROC, wndproc[&hwnd]) };
-
-            if result == 0 {
-                panic!("failed to remove window procedure in KeyboardListener::drop")
-            }
-        } else {
-            panic!("RwLock poisoned in KeyboardListener::drop ")
-        }
+        let _ = self.detach();
     }
 }
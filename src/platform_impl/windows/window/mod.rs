@@ -5,17 +5,19 @@ use std::sync::RwLock;
 
 use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
 use windows::Win32::UI::WindowsAndMessaging::{
-    CallWindowProcW, WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP,
+    CallWindowProcW, DefWindowProcW, WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS,
+    WM_SETFOCUS, WM_SYSKEYDOWN, WM_SYSKEYUP,
 };
 
-use super::handle_key_message;
+pub(crate) use self::keyboard_listener_impl::window_handle;
+use super::{handle_focus_message, handle_key_message, handle_layout_message};
 
 lazy_static::lazy_static! {
     // key: HWND, value: prev window func
     pub static ref WINDOW_SUBCLASSES: RwLock<HashMap<isize, isize>> = RwLock::new(HashMap::new());
 }
 
-// window procecure shared between both the keyboard listener and hotkey listener
+// window procecure shared between every keyboard listener
 pub(crate) unsafe extern "system" fn h_wndproc(
     hwnd: HWND,
     umsg: u32,
@@ -23,17 +25,22 @@ pub(crate) unsafe extern "system" fn h_wndproc(
     lparam: LPARAM,
 ) -> LRESULT {
     if let Ok(subclass) = WINDOW_SUBCLASSES.read() {
-        if let msg @ (WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP) = umsg {
-            handle_key_message(msg, hwnd, wparam)
+        // a message may still arrive while the listener is being detached
+        let Some(prev) = subclass.get(&hwnd.0) else {
+            return DefWindowProcW(hwnd, umsg, wparam, lparam);
+        };
+
+        match umsg {
+            msg @ (WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP) => {
+                handle_key_message(msg, hwnd, wparam, lparam)
+            },
+            WM_SETFOCUS => handle_focus_message(hwnd, true),
+            WM_KILLFOCUS => handle_focus_message(hwnd, false),
+            WM_INPUTLANGCHANGE => handle_layout_message(hwnd, lparam),
+            _ => (),
         }
 
-        CallWindowProcW(
-            std::mem::transmute(subclass[&hwnd.0]),
-            hwnd,
-            umsg,
-            wparam,
-            lparam,
-        )
+        CallWindowProcW(std::mem::transmute(*prev), hwnd, umsg, wparam, lparam)
     } else {
         LRESULT(1)
     }
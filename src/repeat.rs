@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// counts the presses of every key that is held down, so holding two keys at once or
+// releasing one while another repeats doesn't mix up their counts.
+// `K` is whatever identifies a physical key on the platform, e.g. a keycode or scan code
+#[derive(Debug)]
+pub(crate) struct RepeatTracker<K> {
+    // key: physical key, value: number of presses since the key went down
+    held: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq> RepeatTracker<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            held: HashMap::new(),
+        }
+    }
+
+    // returns the repeat count of this press, which is 0 when the key wasn't already down
+    pub(crate) fn press(&mut self, key: K) -> usize {
+        let count = self.held.entry(key).or_insert(0);
+        let repeat_count = *count;
+        *count += 1;
+
+        repeat_count
+    }
+
+    pub(crate) fn release(&mut self, key: &K) {
+        self.held.remove(key);
+    }
+
+    // for when the releases won't arrive, e.g. after losing focus
+    pub(crate) fn clear(&mut self) {
+        self.held.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_while_held() {
+        let mut repeats = RepeatTracker::new();
+
+        assert_eq!(repeats.press(1), 0);
+        assert_eq!(repeats.press(1), 1);
+        assert_eq!(repeats.press(1), 2);
+    }
+
+    #[test]
+    fn press_after_release() {
+        let mut repeats = RepeatTracker::new();
+
+        assert_eq!(repeats.press(1), 0);
+        assert_eq!(repeats.press(1), 1);
+        repeats.release(&1);
+
+        assert_eq!(repeats.press(1), 0);
+    }
+
+    #[test]
+    fn interleaved_keys() {
+        let mut repeats = RepeatTracker::new();
+
+        assert_eq!(repeats.press(1), 0);
+        assert_eq!(repeats.press(2), 0);
+        assert_eq!(repeats.press(1), 1);
+        assert_eq!(repeats.press(2), 1);
+
+        // the other key keeps repeating
+        repeats.release(&1);
+        assert_eq!(repeats.press(2), 2);
+        assert_eq!(repeats.press(1), 0);
+    }
+
+    #[test]
+    fn release_without_press() {
+        let mut repeats = RepeatTracker::new();
+
+        repeats.release(&1);
+        assert_eq!(repeats.press(1), 0);
+    }
+
+    #[test]
+    fn clear() {
+        let mut repeats = RepeatTracker::new();
+
+        repeats.press(1);
+        repeats.press(2);
+        repeats.press(2);
+        repeats.clear();
+
+        assert_eq!(repeats.press(1), 0);
+        assert_eq!(repeats.press(2), 0);
+    }
+}
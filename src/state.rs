@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::{Code, Event, Key, KeyEvent, Modifiers};
+
+// toggled by pressing their key rather than held
+const LOCKS: Modifiers = Modifiers::CAPS_LOCK
+    .union(Modifiers::NUM_LOCK)
+    .union(Modifiers::SCROLL_LOCK);
+
+/// The keys held down and the active modifiers, e.g. for asking "is W down?" once per frame
+/// instead of handling every event.
+///
+/// See: [`KeyboardListener::state`](crate::KeyboardListener::state)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyboardState {
+    // key: platform scan code, value: the event it was pressed with. the code isn't used since
+    // every key without one is `Code::Unidentified`
+    keys: HashMap<u32, KeyEvent>,
+    modifiers: Modifiers,
+}
+
+impl KeyboardState {
+    /// Whether a key held down is `key`, characters are compared without case since
+    /// [`KeyEvent::key`](crate::KeyEvent::key) has no modifiers applied.
+    pub fn is_pressed(&self, key: &Key) -> bool {
+        self.keys.values().any(|k| match (&k.key, key) {
+            (Key::Character(k), Key::Character(key)) => k.to_lowercase() == key.to_lowercase(),
+            (k, key) => k == key,
+        })
+    }
+
+    /// Whether the physical key `code` is held down, which doesn't depend on the layout.
+    pub fn is_code_pressed(&self, code: Code) -> bool {
+        self.keys.values().any(|k| k.code == code)
+    }
+
+    pub fn pressed_keys(&self) -> impl Iterator<Item = &Key> {
+        self.keys.values().map(|k| &k.key)
+    }
+
+    /// Every key held down, so [`Code::Unidentified`] may be there more than once.
+    pub fn pressed_codes(&self) -> impl Iterator<Item = Code> + '_ {
+        self.keys.values().map(|k| k.code)
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    // from keys the OS was asked about
+    pub(crate) fn new(keys: impl IntoIterator<Item = KeyEvent>, modifiers: Modifiers) -> Self {
+        Self {
+            keys: keys.into_iter().map(|k| (k.raw.scancode(), k)).collect(),
+            modifiers,
+        }
+    }
+
+    // releases for every key held down, which are applied like real ones. other keys are
+    // released before the modifiers, so e.g. ctrl+A is released the same way it was pressed,
+    // and the most recently pressed first. each has the modifiers left by the ones before it
+    pub(crate) fn synthetic_releases(&self) -> Vec<Event> {
+        let mut keys: Vec<&KeyEvent> = self.keys.values().collect();
+        keys.sort_by_key(|key| {
+            (
+                !key_modifier(&key.key).is_empty(),
+                std::cmp::Reverse(key.timestamp),
+            )
+        });
+
+        let mut state = self.clone();
+
+        keys.into_iter()
+            .map(|key| {
+                let release = KeyEvent {
+                    modifiers: state.modifiers,
+                    timestamp: SystemTime::now(),
+                    text: None,
+                    composed: None,
+                    synthetic: true,
+                    ..key.clone()
+                };
+                state.update_key(&release, false);
+
+                Event::Release(release)
+            })
+            .collect()
+    }
+
+    // applies `event`, returns it followed by the modifier events it causes.
+    // a `ModifiersChanged` that doesn't change anything is dropped
+    pub(crate) fn apply(&mut self, event: Event) -> Vec<Event> {
+        let previous = self.modifiers;
+        let mut events = Vec::new();
+
+        match &event {
+            Event::Press { key, .. } => self.update_key(key, true),
+            Event::Release(key) => self.update_key(key, false),
+            Event::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            Event::LockChanged { .. }
+            | Event::FocusGained
+            | Event::FocusLost
+            | Event::LayoutChanged(_)
+            | Event::CompositionStart
+            | Event::CompositionUpdate { .. }
+            | Event::CompositionEnd(_) => (),
+        }
+
+        if !matches!(event, Event::ModifiersChanged(_)) {
+            events.push(event);
+        }
+
+        if self.modifiers != previous {
+            events.push(Event::ModifiersChanged(self.modifiers));
+
+            for lock in [
+                Modifiers::CAPS_LOCK,
+                Modifiers::NUM_LOCK,
+                Modifiers::SCROLL_LOCK,
+            ] {
+                if (self.modifiers ^ previous).contains(lock) {
+                    events.push(Event::LockChanged {
+                        lock,
+                        locked: self.modifiers.contains(lock),
+                    });
+                }
+            }
+        }
+
+        events
+    }
+
+    // depending on the platform, the modifiers of a key event may be from before its key was
+    // pressed or released, so the key itself is applied on top of them
+    fn update_key(&mut self, key: &KeyEvent, pressed: bool) {
+        if pressed {
+            self.keys.insert(key.raw.scancode(), key.clone());
+        } else {
+            self.keys.remove(&key.raw.scancode());
+        }
+
+        // e.g. releasing the left shift while the right one is still held
+        let held = self
+            .keys
+            .values()
+            .fold(Modifiers::empty(), |all, k| all | key_modifier(&k.key))
+            & !LOCKS;
+
+        // whether a lock key turns its lock on or off is only known from the state updates
+        // of the backend (see: `Event::ModifiersChanged`), so its own lock is left as it is
+        let own_lock = key_modifier(&key.key) & LOCKS;
+        let locks = (key.modifiers & LOCKS & !own_lock) | (self.modifiers & own_lock);
+
+        self.modifiers = (key.modifiers & !LOCKS & !key_modifier(&key.key)) | held | locks;
+    }
+}
+
+// the modifier set by a key, empty if it isn't a modifier key
+fn key_modifier(key: &Key) -> Modifiers {
+    match key {
+        Key::Alt => Modifiers::ALT,
+        Key::AltGraph => Modifiers::ALT_GRAPH,
+        Key::Control => Modifiers::CONTROL,
+        Key::Shift => Modifiers::SHIFT,
+        // the logo key sets both, the same as in key events
+        Key::Super | Key::Meta => Modifiers::SUPER | Modifiers::META,
+        Key::CapsLock => Modifiers::CAPS_LOCK,
+        Key::NumLock => Modifiers::NUM_LOCK,
+        Key::ScrollLock => Modifiers::SCROLL_LOCK,
+        _ => Modifiers::empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // scan codes of the two shift keys, which only have to differ
+    const SHIFT_LEFT: u32 = 1;
+    const SHIFT_RIGHT: u32 = 2;
+    const KEY_A: u32 = 3;
+
+    fn press(key: KeyEvent) -> Event {
+        Event::Press {
+            key,
+            repeat_count: 0,
+        }
+    }
+
+    fn shift(scancode: u32, modifiers: Modifiers) -> KeyEvent {
+        let code = if scancode == SHIFT_LEFT {
+            Code::ShiftLeft
+        } else {
+            Code::ShiftRight
+        };
+
+        KeyEvent::test(Key::Shift, code, scancode, modifiers)
+    }
+
+    fn a(modifiers: Modifiers) -> KeyEvent {
+        KeyEvent::test(
+            Key::Character("a".to_string()),
+            Code::KeyA,
+            KEY_A,
+            modifiers,
+        )
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut state = KeyboardState::default();
+
+        state.apply(press(a(Modifiers::empty())));
+        assert!(state.is_pressed(&Key::Character("A".to_string())));
+        assert!(state.is_code_pressed(Code::KeyA));
+
+        // the release may have other modifiers and no code, the scan code is what's compared
+        let mut release = a(Modifiers::SHIFT);
+        release.code = Code::Unidentified;
+        state.apply(Event::Release(release));
+        assert!(!state.is_code_pressed(Code::KeyA));
+        assert_eq!(state.pressed_keys().count(), 0);
+    }
+
+    #[test]
+    fn both_shift_keys() {
+        let mut state = KeyboardState::default();
+
+        state.apply(press(shift(SHIFT_LEFT, Modifiers::empty())));
+        state.apply(press(shift(SHIFT_RIGHT, Modifiers::SHIFT)));
+        assert_eq!(state.pressed_keys().count(), 2);
+        assert!(state.is_code_pressed(Code::ShiftLeft));
+        assert!(state.is_code_pressed(Code::ShiftRight));
+
+        // the right one still holds shift
+        state.apply(Event::Release(shift(SHIFT_LEFT, Modifiers::SHIFT)));
+        assert!(!state.is_code_pressed(Code::ShiftLeft));
+        assert!(state.is_pressed(&Key::Shift));
+        assert_eq!(state.modifiers(), Modifiers::SHIFT);
+
+        state.apply(Event::Release(shift(SHIFT_RIGHT, Modifiers::SHIFT)));
+        assert!(!state.is_pressed(&Key::Shift));
+        assert_eq!(state.modifiers(), Modifiers::empty());
+    }
+
+    #[test]
+    fn modifiers_changed_once() {
+        let mut state = KeyboardState::default();
+
+        // the modifiers of a key event are from before it was pressed on some platforms
+        let shift = press(shift(SHIFT_LEFT, Modifiers::empty()));
+        assert_eq!(
+            state.apply(shift.clone()),
+            vec![shift, Event::ModifiersChanged(Modifiers::SHIFT)]
+        );
+
+        // e.g. X11 sends one for every state update, whether it changed or not
+        assert_eq!(
+            state.apply(Event::ModifiersChanged(Modifiers::SHIFT)),
+            vec![]
+        );
+        assert_eq!(
+            state.apply(Event::ModifiersChanged(Modifiers::SHIFT)),
+            vec![]
+        );
+
+        let a = press(a(Modifiers::SHIFT));
+        assert_eq!(state.apply(a.clone()), vec![a]);
+
+        assert_eq!(
+            state.apply(Event::ModifiersChanged(Modifiers::empty())),
+            vec![Event::ModifiersChanged(Modifiers::empty())]
+        );
+        assert_eq!(
+            state.apply(Event::ModifiersChanged(Modifiers::empty())),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn lock_changed() {
+        let mut state = KeyboardState::default();
+
+        // the lock is only known to be on once the backend says so
+        let caps = KeyEvent::test(Key::CapsLock, Code::CapsLock, 4, Modifiers::empty());
+        assert_eq!(state.apply(press(caps.clone())), vec![press(caps.clone())]);
+        assert_eq!(
+            state.apply(Event::ModifiersChanged(Modifiers::CAPS_LOCK)),
+            vec![
+                Event::ModifiersChanged(Modifiers::CAPS_LOCK),
+                Event::LockChanged {
+                    lock: Modifiers::CAPS_LOCK,
+                    locked: true,
+                },
+            ]
+        );
+
+        // releasing the key leaves it on, even with the modifiers from before it was pressed
+        assert_eq!(
+            state.apply(Event::Release(caps.clone())),
+            vec![Event::Release(caps)]
+        );
+        assert_eq!(state.modifiers(), Modifiers::CAPS_LOCK);
+
+        // a key event can turn a lock on or off as well
+        let a = press(a(Modifiers::NUM_LOCK));
+        assert_eq!(
+            state.apply(a.clone()),
+            vec![
+                a,
+                Event::ModifiersChanged(Modifiers::NUM_LOCK),
+                Event::LockChanged {
+                    lock: Modifiers::CAPS_LOCK,
+                    locked: false,
+                },
+                Event::LockChanged {
+                    lock: Modifiers::NUM_LOCK,
+                    locked: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn synthetic_releases() {
+        let mut state = KeyboardState::default();
+
+        let mut control = KeyEvent::test(Key::Control, Code::ControlLeft, 5, Modifiers::empty());
+        let mut a = a(Modifiers::CONTROL);
+        let mut shift = shift(SHIFT_LEFT, Modifiers::CONTROL);
+        control.timestamp = SystemTime::UNIX_EPOCH;
+        a.timestamp = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(2);
+        shift.timestamp = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1);
+
+        for key in [control, shift, a] {
+            state.apply(press(key));
+        }
+        assert_eq!(state.modifiers(), Modifiers::CONTROL | Modifiers::SHIFT);
+
+        let releases = state.synthetic_releases();
+        let released: Vec<_> = releases
+            .iter()
+            .map(|event| match event {
+                Event::Release(key) => {
+                    assert!(key.synthetic);
+                    assert_eq!(key.text, None);
+                    (key.code, key.modifiers)
+                },
+                event => panic!("not a release: {event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            released,
+            [
+                (Code::KeyA, Modifiers::CONTROL | Modifiers::SHIFT),
+                (Code::ShiftLeft, Modifiers::CONTROL | Modifiers::SHIFT),
+                (Code::ControlLeft, Modifiers::CONTROL),
+            ]
+        );
+
+        // they are applied like real ones
+        let events: Vec<_> = releases
+            .into_iter()
+            .flat_map(|release| state.apply(release))
+            .filter(|event| !matches!(event, Event::Release(_)))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                Event::ModifiersChanged(Modifiers::CONTROL),
+                Event::ModifiersChanged(Modifiers::empty()),
+            ]
+        );
+        assert_eq!(state.pressed_keys().count(), 0);
+        assert_eq!(state.modifiers(), Modifiers::empty());
+        assert!(state.synthetic_releases().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+// shared by the integration tests, which need a display server (or `/dev/uinput`) and are
+// `#[ignore]`d. run them with e.g. `xvfb-run cargo test -- --ignored`
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+
+use crosskey::{Event, KeyboardListener};
+use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use winit::event_loop::{EventLoop, EventLoopBuilder};
+use winit::platform::pump_events::EventLoopExtPumpEvents;
+use winit::window::{Window, WindowBuilder};
+use x11rb::protocol::xproto::{ConnectionExt, InputFocus};
+use x11rb::CURRENT_TIME;
+
+// how long to wait for an event before failing
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// a window on its own event loop, which winit only allows once per process
+pub struct TestWindow {
+    event_loop: EventLoop<()>,
+    pub window: Window,
+}
+
+impl TestWindow {
+    pub fn x11() -> Self {
+        use winit::platform::x11::EventLoopBuilderExtX11;
+
+        let event_loop = EventLoopBuilder::new()
+            .with_x11()
+            .with_any_thread(true)
+            .build()
+            .expect("failed to connect to the X server");
+
+        Self::new(event_loop)
+    }
+
+    pub fn wayland() -> Self {
+        use winit::platform::wayland::EventLoopBuilderExtWayland;
+
+        let event_loop = EventLoopBuilder::new()
+            .with_wayland()
+            .with_any_thread(true)
+            .build()
+            .expect("failed to connect to the wayland compositor");
+
+        Self::new(event_loop)
+    }
+
+    fn new(event_loop: EventLoop<()>) -> Self {
+        let window = WindowBuilder::new()
+            .with_title("crosskey test")
+            .build(&event_loop)
+            .expect("failed to create window");
+
+        let mut slf = Self { event_loop, window };
+        slf.pump();
+
+        slf
+    }
+
+    // lets winit map the window and answer the server
+    pub fn pump(&mut self) {
+        self.event_loop
+            .pump_events(Some(Duration::from_millis(50)), |_, _| ());
+    }
+
+    // there may be no window manager to focus the window, so the server is asked directly
+    pub fn focus_x11(&mut self) {
+        let window = match self.window.window_handle().map(|h| h.as_raw()) {
+            Ok(RawWindowHandle::Xlib(handle)) => handle.window as u32,
+            Ok(RawWindowHandle::Xcb(handle)) => handle.window.get(),
+            _ => panic!("not an X11 window"),
+        };
+
+        let (conn, _) = x11rb::connect(None).expect("failed to connect to the X server");
+        conn.set_input_focus(InputFocus::PARENT, window, CURRENT_TIME)
+            .expect("failed to focus window")
+            .check()
+            .expect("failed to focus window");
+
+        self.pump();
+    }
+
+    // pumps the window until `f` is true, false if it wasn't in time
+    pub fn pump_until(&mut self, mut f: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while Instant::now() < deadline {
+            self.pump();
+
+            if f() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // the first event `f` returns `Some` for, `None` if there wasn't one in time
+    pub fn wait_for<T>(
+        &mut self,
+        listener: &KeyboardListener,
+        mut f: impl FnMut(Event) -> Option<T>,
+    ) -> Option<T> {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while Instant::now() < deadline {
+            self.pump();
+
+            for event in listener.poll().ok()? {
+                if let Some(t) = f(event) {
+                    return Some(t);
+                }
+            }
+        }
+
+        None
+    }
+}
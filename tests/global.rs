@@ -0,0 +1,57 @@
+#![cfg(all(feature = "global", target_os = "linux"))]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crosskey::{Code, Event, GlobalKeyboardListener};
+use evdev::uinput::VirtualDeviceBuilder;
+use evdev::{AttributeSet, EventType, InputEvent, Key};
+
+#[test]
+#[ignore = "needs read access to `/dev/input` and write access to `/dev/uinput`"]
+fn global_listener() {
+    let listener = GlobalKeyboardListener::new().expect("failed to create listener");
+
+    // created after the listener so it is picked up by hot-plugging
+    let mut keys = AttributeSet::<Key>::new();
+    keys.insert(Key::KEY_A);
+
+    let mut device = VirtualDeviceBuilder::new()
+        .and_then(|builder| builder.name("crosskey test keyboard").with_keys(&keys))
+        .and_then(|builder| builder.build())
+        .expect("failed to create uinput device");
+
+    // udev may take a moment to make the node readable
+    thread::sleep(Duration::from_secs(1));
+
+    for value in [1, 0] {
+        device
+            .emit(&[InputEvent::new(EventType::KEY, Key::KEY_A.code(), value)])
+            .expect("failed to send key");
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut events = Vec::new();
+
+    while events.len() < 2 && Instant::now() < deadline {
+        let event = listener
+            .recv_timeout(Duration::from_millis(100))
+            .expect("failed to receive");
+
+        // other keyboards may be typed on while the test runs
+        events.extend(event.filter(|e| match e {
+            Event::Press { key, .. } | Event::Release(key) => key.code == Code::KeyA,
+            _ => false,
+        }));
+    }
+
+    assert!(matches!(
+        &events[..],
+        [Event::Press { key: press, repeat_count: 0 }, Event::Release(release)]
+            if press.text.is_some() && release.text.is_none()
+    ));
+    assert!(!listener
+        .state()
+        .expect("failed to get state")
+        .is_code_pressed(Code::KeyA));
+}
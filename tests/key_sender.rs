@@ -0,0 +1,66 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::TestWindow;
+use crosskey::{Event, Key, KeyEvent, KeySender, KeyboardListener, Modifiers};
+
+// the presses of anything but modifier keys
+fn press(event: Event) -> Option<KeyEvent> {
+    match event {
+        Event::Press { key, .. } if !matches!(key.key, Key::Shift | Key::Control) => Some(key),
+        _ => None,
+    }
+}
+
+#[test]
+#[ignore = "needs an X server with a US layout, e.g. `xvfb-run cargo test -- --ignored`"]
+fn key_sender_round_trip() {
+    let mut window = TestWindow::x11();
+    let listener = KeyboardListener::attatch(&window.window).expect("failed to attach");
+
+    // XTest sends to the focused window
+    window.focus_x11();
+    assert_eq!(
+        window.wait_for(&listener, |e| (e == Event::FocusGained).then_some(())),
+        Some(())
+    );
+
+    let sender = KeySender::new().expect("failed to create sender");
+
+    sender
+        .send(&Key::Character("a".to_string()), Modifiers::empty())
+        .expect("failed to send");
+    let key = window.wait_for(&listener, press).expect("no press");
+    assert_eq!(key.key, Key::Character("a".to_string()));
+    assert_eq!(key.text.as_deref(), Some("a"));
+    assert!(!key.modifiers.contains(Modifiers::SHIFT));
+
+    // shift is held for the second level
+    sender
+        .send(&Key::Character("A".to_string()), Modifiers::empty())
+        .expect("failed to send");
+    let key = window.wait_for(&listener, press).expect("no press");
+    assert_eq!(key.key, Key::Character("a".to_string()));
+    assert_eq!(key.text.as_deref(), Some("A"));
+    assert!(key.modifiers.contains(Modifiers::SHIFT));
+
+    sender
+        .send(&Key::Enter, Modifiers::CONTROL)
+        .expect("failed to send");
+    let key = window.wait_for(&listener, press).expect("no press");
+    assert_eq!(key.key, Key::Enter);
+    assert!(key.modifiers.contains(Modifiers::CONTROL));
+
+    sender.type_text("hi").expect("failed to type");
+    for c in ["h", "i"] {
+        let key = window.wait_for(&listener, press).expect("no press");
+        assert_eq!(key.text.as_deref(), Some(c));
+    }
+
+    // every key sent was released again
+    assert!(window.pump_until(|| {
+        let state = listener.state().expect("failed to get state");
+        state.pressed_keys().count() == 0
+    }));
+}
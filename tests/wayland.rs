@@ -0,0 +1,35 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::thread;
+
+use common::TestWindow;
+use crosskey::{KeyboardListener, LayoutId, ReceiveError};
+
+#[test]
+#[ignore = "needs a wayland compositor with a keyboard, e.g. `weston --backend=headless`"]
+fn wayland_listener() {
+    let mut window = TestWindow::wayland();
+    let listener = KeyboardListener::attatch(&window.window).expect("failed to attach");
+
+    // the compositor sends the keymap once the keyboard has been bound
+    assert!(window.pump_until(|| !listener.layouts().is_empty()));
+
+    // wayland only sends the compiled keymap, not the names it was compiled from
+    let layouts = listener.layouts();
+    assert!(matches!(&layouts[0].id, LayoutId::Xkb { layout, .. } if layout.is_empty()));
+
+    // wayland doesn't let clients ask which keys are down
+    assert!(listener.query_state().is_none());
+
+    let receiving = listener.clone();
+    let thread = thread::spawn(move || receiving.try_recv(|_| ()));
+
+    listener.handle().detach().expect("failed to detach");
+
+    assert!(matches!(
+        thread.join().expect("receiving thread panicked"),
+        Err(ReceiveError::ListenerClosed)
+    ));
+}
@@ -0,0 +1,93 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::process::Command;
+use std::thread;
+
+use common::TestWindow;
+use crosskey::{Event, KeyboardListener, LayoutId, ReceiveError};
+
+#[test]
+#[ignore = "needs an X server, e.g. `xvfb-run cargo test -- --ignored`"]
+fn x11_listener() {
+    let mut window = TestWindow::x11();
+    let listener = KeyboardListener::attatch(&window.window).expect("failed to attach");
+
+    // the keymap is fetched from the server when attaching
+    let layouts = listener.layouts();
+    assert!(!layouts.is_empty());
+    assert!(matches!(layouts[0].id, LayoutId::Xkb { .. }));
+    assert_eq!(listener.active_layout().as_ref(), layouts.first());
+
+    let state = listener.query_state().expect("failed to query the server");
+    assert_eq!(state.pressed_keys().count(), 0);
+
+    window.focus_x11();
+    assert_eq!(
+        window.wait_for(&listener, |e| (e == Event::FocusGained).then_some(())),
+        Some(())
+    );
+
+    // detaching wakes up every thread blocked receiving
+    let receiving = listener.clone();
+    let thread = thread::spawn(move || receiving.try_recv(|_| ()));
+
+    listener.handle().detach().expect("failed to detach");
+
+    assert!(matches!(
+        thread.join().expect("receiving thread panicked"),
+        Err(ReceiveError::ListenerClosed)
+    ));
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. `xvfb-run cargo test -- --ignored`"]
+fn x11_listeners_share_window() {
+    let mut window = TestWindow::x11();
+    let first = KeyboardListener::attatch(&window.window).expect("failed to attach");
+    let second = KeyboardListener::attatch(&window.window).expect("failed to attach");
+
+    window.focus_x11();
+    for listener in [&first, &second] {
+        assert_eq!(
+            window.wait_for(listener, |e| (e == Event::FocusGained).then_some(())),
+            Some(())
+        );
+    }
+
+    // the other listener keeps receiving
+    first.handle().detach().expect("failed to detach");
+    assert!(matches!(first.state(), Err(ReceiveError::ListenerClosed)));
+    assert!(!second.layouts().is_empty());
+}
+
+// changes the server's keymap, which every client is told about
+fn setxkbmap(layout: &str) {
+    let status = Command::new("setxkbmap")
+        .arg(layout)
+        .status()
+        .expect("failed to run setxkbmap");
+
+    assert!(status.success(), "setxkbmap {layout} failed");
+}
+
+#[test]
+#[ignore = "needs an X server and setxkbmap, e.g. `xvfb-run cargo test -- --ignored`"]
+fn x11_layout_changed() {
+    setxkbmap("us");
+
+    let mut window = TestWindow::x11();
+    let listener = KeyboardListener::attatch(&window.window).expect("failed to attach");
+
+    setxkbmap("de");
+    let layout = window.wait_for(&listener, |e| match e {
+        Event::LayoutChanged(layout) => Some(layout),
+        _ => None,
+    });
+    setxkbmap("us");
+
+    let layout = layout.expect("no layout change");
+    assert!(matches!(&layout.id, LayoutId::Xkb { layout, .. } if layout == "de"));
+    assert_eq!(listener.active_layout(), Some(layout));
+}